@@ -1,6 +1,5 @@
 use super::{Error, Result};
-use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde_derive::{Deserialize, Serialize};
 
@@ -23,25 +22,31 @@ impl Ace {
     /// If a user both set the config in `book.toml` and have `ace-*.css` file,
     /// the config will be ignored.
     pub fn css_class_text(&self, dark: bool) -> Result<(String, String)> {
-        let mut css_text = String::new();
+        let mut css_text;
         let ace_file = format!("ace-{}.css", if dark { "dark" } else { "white" });
-        let path = self.theme_dir.join(ace_file);
+        let mut path = self.theme_dir.join(ace_file);
+        if !path.exists() {
+            path = self.theme_dir.join("ace.css");
+        }
 
-        if path.exists() || self.theme_dir.join("ace.css").exists() {
-            std::fs::File::open(path)
-                .unwrap()
-                .read_to_string(&mut css_text)
-                .unwrap();
+        if path.exists() {
+            css_text = read(&path)?;
         } else if let Some(v) = self.defult_css(dark) {
             css_text = String::from(unsafe { std::str::from_utf8_unchecked(v) });
         } else {
-            return Err(Error::AceNotFound);
+            let name = if dark {
+                &self.theme_dark
+            } else {
+                &self.theme_white
+            };
+            return Err(Error::AceNotFound(name.clone()));
         }
 
         css_text = css_text.replace(['\n', '"'], " ");
-        let p1 = css_text.find(".ace-").ok_or(Error::StrNotFound)?;
+        let not_found = || Error::StrNotFound("`.ace-*` css class in the ace theme".into());
+        let p1 = css_text.find(".ace-").ok_or_else(not_found)?;
         let css_class =
-            css_text[p1 + 1..p1 + css_text[p1..].find(' ').ok_or(Error::StrNotFound)?].to_string();
+            css_text[p1 + 1..p1 + css_text[p1..].find(' ').ok_or_else(not_found)?].to_string();
         Ok((css_class, css_text))
     }
 
@@ -66,15 +71,14 @@ impl Ace {
         let path = &self.build_dir.join("html").join(file);
 
         let (css_class, css_text) = css_;
-        let mut content = String::new();
-        std::fs::File::open(path)
-            .unwrap()
-            .read_to_string(&mut content)
-            .unwrap();
-        content.replace_range(find(&content, "cssClass=\"")?, &css_class);
-        content.replace_range(find(&content, "cssText=\"")?, &css_text);
-
-        std::fs::write(path, content).map_err(|_| Error::FileNotWritten)?;
+        let item = if dark { "theme-dark" } else { "theme-white" };
+        let mut content = read(path)?;
+        let range = find(&content, "cssClass=\"").map_err(|e| e.config(item, path))?;
+        content.replace_range(range, &css_class);
+        let range = find(&content, "cssText=\"").map_err(|e| e.config(item, path))?;
+        content.replace_range(range, &css_text);
+
+        std::fs::write(path, content).map_err(|e| Error::FileNotWritten(path.clone(), e))?;
         Ok(())
     }
 
@@ -93,8 +97,9 @@ impl Ace {
         if self.below_build_dir {
             use mdbook::utils::fs::copy_files_except_ext as copy;
             let html = self.build_dir.join("html");
-            copy(&html, &self.build_dir, true, None, &[]).map_err(|_| Error::DirNotCreated)?;
-            std::fs::remove_dir_all(html).map_err(|_| Error::DirNotRemoved)?;
+            copy(&html, &self.build_dir, true, None, &[])
+                .map_err(|e| Error::DirNotCreated(self.build_dir.clone(), e.into()))?;
+            std::fs::remove_dir_all(&html).map_err(|e| Error::DirNotRemoved(html, e))?;
         }
         Ok(())
     }
@@ -122,11 +127,16 @@ impl Default for Ace {
 /// find the positions of double quotation marks behind cssClass or cssText
 /// target: "cssClass=\"" | "cssText=\""
 fn find(content: &str, target: &str) -> Result<std::ops::Range<usize>> {
-    let p1 = content.find(target).ok_or(Error::StrNotFound)? + target.len();
-    let p2 = p1 + content[p1..].find('"').ok_or(Error::StrNotFound)?;
+    let not_found = || Error::StrNotFound(format!("`{target}...\"`"));
+    let p1 = content.find(target).ok_or_else(not_found)? + target.len();
+    let p2 = p1 + content[p1..].find('"').ok_or_else(not_found)?;
     Ok(p1..p2)
 }
 
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| Error::FileNotRead(path.to_path_buf(), e))
+}
+
 default! {
     "./ace/theme/ambiance.css",                AMBIANCE;
     "./ace/theme/chaos.css",                   CHAOS;
@@ -1,13 +1,33 @@
 use mdbook::renderer::RenderContext;
 use mdbook_theme::{ace::Ace, theme_dir, Error, Result};
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let ctx = RenderContext::from_json(std::io::stdin()).map_err(|_| Error::MdbookNotParsed)?;
+fn main() -> ExitCode {
+    run().map_or_else(
+        |err| {
+            // print the error with its causes
+            eprintln!("{:?}", mdbook::errors::Error::from(err));
+            ExitCode::from(1)
+        },
+        |_| ExitCode::from(0),
+    )
+}
+
+fn run() -> Result<()> {
+    let key = "output.theme-ace";
+    let ctx =
+        RenderContext::from_json(std::io::stdin()).map_err(|e| Error::MdbookNotParsed(e.into()))?;
     let mut cfg: Ace = ctx
         .config
-        .get_deserialized_opt("output.theme-ace")
-        .map_err(|_| Error::DeserializedFailed)?
-        .ok_or(Error::DeserializedFailed)?;
+        .get_deserialized_opt(key)
+        .map_err(|e| Error::DeserializedFailed {
+            key: key.into(),
+            source: Some(e.into()),
+        })?
+        .ok_or_else(|| Error::DeserializedFailed {
+            key: key.into(),
+            source: None,
+        })?;
     cfg.build_dir = ctx.root.join(&ctx.config.build.build_dir);
     cfg.destination = ctx.root.join(&ctx.destination);
     cfg.theme_dir = theme_dir(&ctx.root, &ctx.config);
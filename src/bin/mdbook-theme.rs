@@ -1,12 +1,16 @@
 // https://github.com/rust-lang/mdBook/blob/efb671aaf241b7f93597ac70178989a332fe85e0/examples/nop-preprocessor.rs
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use mdbook::{
     errors::Error,
     preprocess::{CmdPreprocessor, Preprocessor},
 };
-use mdbook_theme::PreTheme;
+use mdbook_theme::{
+    doctor::{self, Level},
+    theme::manifest::Manifest,
+    theme_dir, PreTheme,
+};
 use semver::{Version, VersionReq};
-use std::{io, process::ExitCode};
+use std::{io, path::PathBuf, process::ExitCode};
 
 type Return = Result<(), Error>;
 
@@ -14,13 +18,50 @@ fn make_app() -> Command {
     let sub = Command::new("supports")
         .about("Check whether a renderer is supported by this preprocessor")
         .arg(Arg::new("renderer").required(true));
+    let undo = |name: &'static str, about: &'static str| {
+        Command::new(name)
+            .about(about)
+            .arg(
+                Arg::new("dir")
+                    .default_value(".")
+                    .help("Root directory of the book"),
+            )
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Also undo files changed after mdbook-theme wrote them"),
+            )
+    };
+    let doctor = Command::new("doctor")
+        .about("Check the theme dir and the config of a book against mdBook's theme files")
+        .arg(
+            Arg::new("dir")
+                .default_value(".")
+                .help("Root directory of the book"),
+        );
     Command::new("mdbook-theme")
         .author("zjp")
         .about(
             "A mdbook preprocessor to config theme for mdbook, \
             especially making a pagetoc on the right.",
         )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Print diffs of theme files instead of writing them"),
+        )
         .subcommand(sub)
+        .subcommand(undo(
+            "clean",
+            "Remove the theme files created by this preprocessor",
+        ))
+        .subcommand(undo(
+            "restore",
+            "Remove the created theme files and restore the modified ones from backups",
+        ))
+        .subcommand(doctor)
 }
 
 // The return value needs to be an ExitCode due to
@@ -29,8 +70,25 @@ fn main() -> ExitCode {
     let matches = make_app().get_matches();
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(sub_args)
+    } else if let Some((name @ ("clean" | "restore"), sub_args)) = matches.subcommand() {
+        handle_undo(name == "restore", sub_args).map_or_else(
+            |err| {
+                eprintln!("{err:?}");
+                ExitCode::from(1)
+            },
+            |_| ExitCode::from(0),
+        )
+    } else if let Some(sub_args) = matches.subcommand_matches("doctor") {
+        // fails when a problem is found
+        handle_doctor(sub_args).map_or_else(
+            |err| {
+                eprintln!("{err:?}");
+                ExitCode::from(1)
+            },
+            |healthy| ExitCode::from(u8::from(!healthy)),
+        )
     } else {
-        handle_preprocessing().map_or_else(
+        handle_preprocessing(matches.get_flag("dry-run")).map_or_else(
             |err| {
                 eprintln!("{err:?}");
                 ExitCode::from(1)
@@ -41,8 +99,12 @@ fn main() -> ExitCode {
 }
 
 // stdin
-fn handle_preprocessing() -> Return {
-    let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
+fn handle_preprocessing(dry_run: bool) -> Return {
+    let (mut ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
+    if dry_run {
+        // the same as `dry-run = true` in `[preprocessor.theme]`
+        ctx.config.set("preprocessor.theme.dry-run", true)?;
+    }
 
     let book_version = Version::parse(&ctx.mdbook_version)?;
     let version_req = VersionReq::parse(mdbook::MDBOOK_VERSION)?;
@@ -74,3 +136,52 @@ fn handle_supports(sub_args: &ArgMatches) -> ExitCode {
         ExitCode::from(1)
     }
 }
+
+// `clean` or `restore` the files recorded in the manifest of the theme dir
+fn handle_undo(restore: bool, sub_args: &ArgMatches) -> Return {
+    let root = PathBuf::from(
+        sub_args
+            .get_one::<String>("dir")
+            .expect("Defaulted argument"),
+    );
+    let config = mdbook::Config::from_disk(root.join("book.toml"))?;
+    let force = sub_args.get_flag("force");
+    let mut manifest = Manifest::load(&theme_dir(&root, &config))?;
+    let report = if restore {
+        manifest.restore(force)?
+    } else {
+        manifest.clean(force)?
+    };
+    if report.is_empty() {
+        println!("nothing to {}", if restore { "restore" } else { "clean" });
+    }
+    for line in report {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+// check the theme dir of a book; `false` if a problem is found
+fn handle_doctor(sub_args: &ArgMatches) -> Result<bool, Error> {
+    let root = PathBuf::from(
+        sub_args
+            .get_one::<String>("dir")
+            .expect("Defaulted argument"),
+    );
+    let config = mdbook::Config::from_disk(root.join("book.toml"))?;
+    println!(
+        "checking `{}` against the theme of mdBook {}",
+        theme_dir(&root, &config).display(),
+        mdbook::MDBOOK_VERSION
+    );
+    let findings = doctor::run(&root, &config)?;
+    for finding in &findings {
+        println!("{finding}");
+    }
+    let problems = findings
+        .iter()
+        .filter(|f| f.level == Level::Problem)
+        .count();
+    println!("{problems} problem(s) found");
+    Ok(problems == 0)
+}
@@ -0,0 +1,412 @@
+//! `mdbook-theme doctor` : check the theme dir of a book against the theme files of mdBook in
+//! this tool, and the config in `book.toml` that the theme dir relies on.
+//!
+//! Copies of mdBook's theme files go stale silently when mdBook is updated, so each one is told
+//! apart as mdBook's, generated by this tool (with the manifest), or customized.
+
+use crate::{
+    ace::Ace,
+    additional,
+    theme::{
+        config::pagetoc_from,
+        default::{MDBOOK_CSS, MDBOOK_OTHERS},
+        layout::Layout,
+        manifest::Manifest,
+        output::changed_lines,
+        overrides::OVERRIDE_CSS,
+        pagetoc,
+    },
+    theme_dir, Error, Result,
+};
+use mdbook::{Config, MDBOOK_VERSION};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    /// worth a look, but the book builds as expected
+    Note,
+    /// the book won't build, or won't look as configured
+    Problem,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub level: Level,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Ok => "ok",
+            Level::Note => "note",
+            Level::Problem => "problem",
+        };
+        write!(f, "{level:>7}: {}", self.message)
+    }
+}
+
+#[derive(Default)]
+struct Report(Vec<Finding>);
+
+impl Report {
+    fn push(&mut self, level: Level, message: String) {
+        self.0.push(Finding { level, message });
+    }
+}
+
+/// Check the theme dir of the book in `root` with its `config` .
+pub fn run(root: &Path, config: &Config) -> Result<Vec<Finding>> {
+    let dir = theme_dir(root, config);
+    let manifest = Manifest::load(&dir)?;
+    let mut report = Report::default();
+    theme_files(&dir, &manifest, &mut report)?;
+    preprocessor(root, config, &dir, &mut report)?;
+    ace(config, &dir, &mut report);
+    Ok(report.0)
+}
+
+/// Copies of mdBook's theme files, and the files this tool wrote.
+fn theme_files(dir: &Path, manifest: &Manifest, report: &mut Report) -> Result<()> {
+    for &(name, stock) in MDBOOK_CSS.iter().chain(MDBOOK_OTHERS) {
+        let path = dir.join(name);
+        if !path.exists() {
+            continue; // mdBook uses its own
+        }
+        let content = read(&path)?;
+        if content == stock {
+            report.push(
+                Level::Ok,
+                format!("`{name}` is the same as mdBook {MDBOOK_VERSION}'s"),
+            );
+            continue;
+        }
+        match manifest.entry(name) {
+            Some(entry) if entry.is_written(&content) && entry.is_created() => {
+                let (level, message) = match entry.mdbook() {
+                    Some(v) if v == MDBOOK_VERSION => (
+                        Level::Ok,
+                        format!("`{name}` is generated by mdbook-theme from mdBook {v}'s"),
+                    ),
+                    Some(v) => (
+                        Level::Problem,
+                        format!(
+                            "`{name}` is stale: generated from mdBook {v}'s, not \
+                             {MDBOOK_VERSION}'s; run `mdbook-theme clean` and build the book \
+                             to generate it again"
+                        ),
+                    ),
+                    None => (
+                        Level::Note,
+                        format!(
+                            "`{name}` is generated by an earlier mdbook-theme from some version \
+                             of mdBook's; run `mdbook-theme clean` and build the book to \
+                             generate it from mdBook {MDBOOK_VERSION}'s"
+                        ),
+                    ),
+                };
+                report.push(level, message);
+            }
+            Some(entry) if entry.is_written(&content) => {
+                // modified by this tool: the copy is what it was before
+                let original = match read(&manifest.backup(name)) {
+                    Ok(original) => original,
+                    Err(_) => {
+                        let message = format!(
+                            "backup of `{name}` is missing: `mdbook-theme restore` can't bring \
+                             back the original, and whether it was mdBook's is unknown"
+                        );
+                        report.push(Level::Problem, message);
+                        continue;
+                    }
+                };
+                if original == stock {
+                    report.push(
+                        Level::Ok,
+                        format!("`{name}` is mdBook {MDBOOK_VERSION}'s modified by mdbook-theme"),
+                    );
+                } else {
+                    customized(name, &original, stock, &path, report);
+                }
+            }
+            _ => customized(name, &content, stock, &path, report),
+        }
+    }
+
+    for name in manifest.names() {
+        if !dir.join(name).exists() {
+            report.push(
+                Level::Note,
+                format!(
+                    "`{name}` written by mdbook-theme is missing; build the book to write it \
+                     again, or run `mdbook-theme clean` to forget it"
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A copy of mdBook's theme file not written by this tool: customized, or from another release.
+fn customized(name: &str, content: &[u8], stock: &[u8], path: &Path, report: &mut Report) {
+    let (text, stock) = match (std::str::from_utf8(content), std::str::from_utf8(stock)) {
+        (Ok(text), Ok(stock)) => (text, stock),
+        _ => {
+            let message = format!("`{name}` differs from mdBook {MDBOOK_VERSION}'s");
+            return report.push(Level::Note, message);
+        }
+    };
+    if name == "index.hbs" {
+        let layout = Layout::bundled();
+        let anchors = [layout.main, layout.content, layout.theme_list];
+        if let Err(e) = layout.check(text, &anchors, path) {
+            return report.push(Level::Problem, e.to_string());
+        }
+    }
+    report.push(
+        Level::Note,
+        format!(
+            "`{name}` differs from mdBook {MDBOOK_VERSION}'s in {} lines: customized, or copied \
+             from another version of mdBook",
+            changed_lines(stock, text)
+        ),
+    );
+}
+
+/// The files `[preprocessor.theme]` needs in `[output.html]` .
+fn preprocessor(root: &Path, config: &Config, dir: &Path, report: &mut Report) -> Result<()> {
+    let theme = match config.get_preprocessor("theme") {
+        Some(theme) => theme,
+        None => {
+            report.push(
+                Level::Note,
+                "[preprocessor.theme] is not in book.toml".to_string(),
+            );
+            return Ok(());
+        }
+    };
+    let on = |key: &str| theme.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    if on("turn-off") {
+        report.push(
+            Level::Note,
+            "`turn-off = true` in [preprocessor.theme]".to_string(),
+        );
+        return Ok(());
+    }
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    let pagetoc = match theme.get("pagetoc").map(pagetoc_from) {
+        Some(Ok(pagetoc)) => pagetoc.is_some(),
+        Some(Err(e)) => {
+            report.push(Level::Problem, e.to_string());
+            true
+        }
+        None => false,
+    };
+    for (kind, file) in [("css", "pagetoc.css"), ("js", "pagetoc.js")] {
+        let path = dir.join(file);
+        let loaded = additional(root, config, kind).contains(&path);
+        let rel = relative(&path);
+        match (pagetoc, loaded) {
+            (true, true) => {
+                let message = format!("`additional-{kind}` loads \"{rel}\"");
+                report.push(Level::Ok, message);
+            }
+            (true, false) => {
+                let message = format!(
+                    "add \"{rel}\" to `additional-{kind}` in [output.html] for the pagetoc"
+                );
+                report.push(Level::Problem, message);
+            }
+            (false, true) => {
+                let message = format!("`additional-{kind}` loads \"{rel}\" without the pagetoc");
+                report.push(Level::Note, message);
+            }
+            (false, false) => continue,
+        }
+        if !path.exists() {
+            let message = format!("`{file}` is not in the theme dir yet: build the book");
+            report.push(Level::Note, message);
+        } else if !pagetoc::is_generated(&String::from_utf8_lossy(&read(&path)?)) {
+            let message = format!("`{file}` is your own, and left untouched by mdbook-theme");
+            report.push(Level::Note, message);
+        }
+    }
+
+    if on("override") {
+        let css = dir.join(OVERRIDE_CSS);
+        let rel = relative(&css);
+        if additional(root, config, "css").contains(&css) {
+            report.push(Level::Ok, format!("`additional-css` loads \"{rel}\""));
+        } else {
+            let message = format!(
+                "add \"{rel}\" to `additional-css` in [output.html] to load the override \
+                 stylesheet"
+            );
+            report.push(Level::Problem, message);
+        }
+    }
+    Ok(())
+}
+
+/// `[output.theme-ace]` for the backend `mdbook-theme-ace` .
+fn ace(config: &Config, dir: &Path, report: &mut Report) {
+    let key = "output.theme-ace";
+    let ace = match config.get_deserialized_opt::<Ace, _>(key) {
+        Ok(Some(ace)) => Ace {
+            theme_dir: dir.to_path_buf(),
+            ..ace
+        },
+        Ok(None) => return,
+        Err(e) => {
+            let e = Error::DeserializedFailed {
+                key: key.into(),
+                source: Some(e.into()),
+            };
+            return report.push(Level::Problem, e.to_string());
+        }
+    };
+
+    if config.get("output.html").is_none() {
+        let message = "[output.html] is needed by [output.theme-ace] , which edits the ace \
+                       themes it renders"
+            .to_string();
+        report.push(Level::Problem, message);
+    }
+    if config
+        .get("output.html.playground.editable")
+        .and_then(|v| v.as_bool())
+        != Some(true)
+    {
+        let message = "the ace editor is only loaded with `editable = true` in \
+                       [output.html.playground]"
+            .to_string();
+        report.push(Level::Note, message);
+    }
+    for (dark, item, name) in [
+        (false, "theme-white", &ace.theme_white),
+        (true, "theme-dark", &ace.theme_dark),
+    ] {
+        let local = local_ace(dir, dark);
+        match (ace.css_class_text(dark), &local) {
+            (Err(e), _) => report.push(Level::Problem, format!("`{item}` : {e}")),
+            (Ok(_), Some(file)) => {
+                let message = format!("`{item}` : `{}` in the theme dir is used", file.display());
+                report.push(Level::Ok, message);
+            }
+            (Ok(_), None) if name.is_empty() => {
+                let message = format!("`{item}` is not set in [output.theme-ace]");
+                report.push(Level::Problem, message);
+            }
+            (Ok((class, _)), None) => {
+                report.push(Level::Ok, format!("`{item}` : `{name}` ({class})"));
+            }
+        }
+    }
+}
+
+/// `ace-dark.css` / `ace-white.css` or `ace.css` in the theme dir, which take the place of the
+/// ace theme set in `[output.theme-ace]` .
+fn local_ace(dir: &Path, dark: bool) -> Option<PathBuf> {
+    let file = if dark {
+        "ace-dark.css"
+    } else {
+        "ace-white.css"
+    };
+    [file, "ace.css"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|f| dir.join(f).exists())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| Error::FileNotRead(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{theme_files, Level, Report};
+    use crate::{
+        test_dir,
+        theme::manifest::{Manifest, MANIFEST},
+    };
+    use mdbook::MDBOOK_VERSION;
+    use std::{fs, path::Path};
+
+    /// what `Output` does for a file
+    fn write(manifest: &mut Manifest, path: &Path, content: &str) {
+        manifest.record(path, content.as_bytes()).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// the findings about the theme files in `dir`
+    fn check(dir: &Path) -> Vec<(Level, String)> {
+        let mut report = Report::default();
+        theme_files(dir, &Manifest::load(dir).unwrap(), &mut report).unwrap();
+        report.0.into_iter().map(|f| (f.level, f.message)).collect()
+    }
+
+    #[test]
+    fn missing_backup() {
+        let dir = test_dir("doctor-backup");
+        let path = dir.join("css/variables.css");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, mdbook::theme::VARIABLES_CSS).unwrap();
+        let mut manifest = Manifest::load(&dir).unwrap();
+        write(&mut manifest, &path, ":root { --bg: red; }");
+        let modified = format!("`css/variables.css` is mdBook {MDBOOK_VERSION}'s modified");
+        assert!(check(&dir)[0].1.starts_with(&modified), "{:?}", check(&dir));
+
+        fs::remove_file(manifest.backup("css/variables.css")).unwrap();
+        let findings = check(&dir);
+        assert_eq!(findings.len(), 1, "{:?}", findings);
+        assert_eq!(findings[0].0, Level::Problem);
+        assert!(findings[0]
+            .1
+            .starts_with("backup of `css/variables.css` is missing"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_and_missing_generated_files() {
+        let dir = test_dir("doctor-stale");
+        let mut manifest = Manifest::load(&dir).unwrap();
+        write(
+            &mut manifest,
+            &dir.join("css/chrome.css"),
+            ".x { color: red; }",
+        );
+        write(&mut manifest, &dir.join("pagetoc.css"), ".pagetoc {}");
+        fs::remove_file(dir.join("pagetoc.css")).unwrap();
+        let findings = check(&dir);
+        assert_eq!(findings[0].0, Level::Ok, "{:?}", findings);
+
+        let json = fs::read_to_string(dir.join(MANIFEST)).unwrap();
+        let version = format!("\"mdbook\": \"{MDBOOK_VERSION}\"");
+        assert!(json.contains(&version), "{}", json);
+        let json = json.replace(&version, "\"mdbook\": \"0.3.0\"");
+        fs::write(dir.join(MANIFEST), json).unwrap();
+        let findings = check(&dir);
+        assert_eq!(findings.len(), 2, "{:?}", findings);
+        assert_eq!(findings[0].0, Level::Problem);
+        let stale = format!(
+            "`css/chrome.css` is stale: generated from mdBook 0.3.0's, not {MDBOOK_VERSION}'s"
+        );
+        assert!(findings[0].1.starts_with(&stale), "{}", findings[0].1);
+        assert_eq!(findings[1].0, Level::Note);
+        assert!(findings[1]
+            .1
+            .starts_with("`pagetoc.css` written by mdbook-theme is missing"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
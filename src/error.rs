@@ -0,0 +1,147 @@
+//! Errors carry what failed (the path, the config item or the anchor text),
+//! so a failure in `mdbook build` reads as a message instead of a panic backtrace.
+
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+type Source = Box<dyn error::Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An anchor (a selector, a property or a piece of text) is not found.
+    StrNotFound(String),
+    FileNotRead(PathBuf, io::Error),
+    FileNotWritten(PathBuf, io::Error),
+    FileNotRemoved(PathBuf, io::Error),
+    DirNotCreated(PathBuf, Source),
+    DirNotRemoved(PathBuf, io::Error),
+    /// The ace theme name is neither built-in nor provided in the theme dir.
+    AceNotFound(String),
+    MdbookNotParsed(Source),
+    /// The manifest of generated files in the theme dir is broken.
+    ManifestNotParsed(PathBuf, Source),
+    DeserializedFailed {
+        key: String,
+        source: Option<Source>,
+    },
+    /// Items in `[preprocessor.theme]` unknown to this tool, with suggestions.
+    UnknownItems(Vec<String>),
+    /// A config value that can't be used, e.g. `sidebar-width = true` .
+    InvalidValue {
+        item: String,
+        reason: String,
+    },
+    /// A `<!-- pagetoc: ... -->` marker in a chapter that can't be used.
+    InvalidMarker {
+        chapter: PathBuf,
+        marker: String,
+        reason: String,
+    },
+    /// The version of mdBook has no known layout of theme files.
+    UnsupportedMdbook(String),
+    /// A theme file doesn't have an anchor of the layout of the running mdBook.
+    LayoutMismatch {
+        file: PathBuf,
+        anchor: String,
+        mdbook: &'static str,
+    },
+    /// A theme file of mdBook is needed in the theme dir, since the copy in this tool has another
+    /// layout.
+    NoThemeFile {
+        file: PathBuf,
+        mdbook: &'static str,
+        bundled: &'static str,
+    },
+    /// Text colours below the contrast ratio set by `contrast` , in strict mode.
+    LowContrast(Vec<String>),
+    /// A variable not defined for a theme class, with similar ones.
+    UnknownVariable {
+        variable: String,
+        selector: String,
+        similar: Vec<String>,
+    },
+    /// Failed to apply a config item to a theme file.
+    Config {
+        item: String,
+        file: PathBuf,
+        source: Box<Error>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Tell which config item and which theme file the error comes from.
+    pub fn config(self, item: &str, file: &Path) -> Self {
+        Error::Config {
+            item: item.to_string(),
+            file: file.to_path_buf(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    #[rustfmt::skip]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            StrNotFound(anchor)         => write!(f, "{anchor} is not found"),
+            FileNotRead(path, _)        => write!(f, "failed to read `{}`", path.display()),
+            FileNotWritten(path, _)     => write!(f, "failed to write `{}`", path.display()),
+            FileNotRemoved(path, _)     => write!(f, "failed to remove `{}`", path.display()),
+            DirNotCreated(path, _)      => write!(f, "failed to create dir `{}`", path.display()),
+            DirNotRemoved(path, _)      => write!(f, "failed to remove dir `{}`", path.display()),
+            AceNotFound(name)           => write!(f, "ace theme `{name}` is neither built-in nor \
+                                                      found in the theme dir"),
+            MdbookNotParsed(_)          => write!(f, "failed to parse the input from mdbook"),
+            ManifestNotParsed(path, _)  => write!(f, "failed to parse `{}`", path.display()),
+            DeserializedFailed { key, .. } => write!(f, "failed to deserialize `{key}` in book.toml"),
+            UnknownItems(items)         => write!(f, "unknown items in [preprocessor.theme]:\n  {}",
+                                                  items.join("\n  ")),
+            InvalidMarker { chapter, marker, reason } => write!(f, "invalid `{marker}` in `{}`: \
+                                                                   {reason}", chapter.display()),
+            UnsupportedMdbook(v)        => write!(f, "mdBook {v} is not supported: mdbook-theme \
+                                                      knows the theme layouts of mdBook 0.4 and \
+                                                      0.5"),
+            LayoutMismatch { file, anchor, mdbook } => write!(f, "`{}` doesn't have `{anchor}` as \
+                                                       in the theme of mdBook {mdbook}: is it from \
+                                                       another version of mdBook?", file.display()),
+            NoThemeFile { file, mdbook, bundled } => write!(f, "`{}` is needed in the theme dir \
+                                                     with mdBook {mdbook}, as the copy in \
+                                                     mdbook-theme is from mdBook {bundled}; run \
+                                                     `mdbook init --theme` to get it",
+                                                     file.display()),
+            LowContrast(pairs)          => write!(f, "low contrast colours:\n  {}",
+                                                  pairs.join("\n  ")),
+            InvalidValue { item, reason }  => write!(f, "invalid value for `{item}`: {reason}"),
+            UnknownVariable { variable, selector, similar } => {
+                write!(f, "`{variable}` is not a variable of `{selector}`")?;
+                match similar.is_empty() {
+                    true  => Ok(()),
+                    false => write!(f, ", did you mean `{}`?", similar.join("` or `")),
+                }
+            }
+            Config { item, file, .. }   => write!(f, "failed to apply `{item}` to `{}`",
+                                                  file.display()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+        match self {
+            FileNotRead(_, e)
+            | FileNotWritten(_, e)
+            | FileNotRemoved(_, e)
+            | DirNotRemoved(_, e) => Some(e),
+            DirNotCreated(_, e) | MdbookNotParsed(e) | ManifestNotParsed(_, e) => Some(e.as_ref()),
+            DeserializedFailed { source, .. } => source.as_ref().map(|e| e.as_ref() as _),
+            Config { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -13,6 +13,9 @@ use std::{
 macro_rules! default {
     ($idt:ident, $e1:expr) => { (CssFile::$idt, $e1) };
     ($idt:ident, $e1:expr, $e2:expr) => { (CssFile::$idt, Item($e1), Value($e2)) };
+    ($idt:ident, $e1:expr, $e2:expr, $ty:ident) => {
+        (CssFile::$idt, Item($e1), Value($e2), ValueType::$ty)
+    };
     ($($e1:expr, $idt:ident);*) => {
         $(pub static $idt: &[u8] = include_bytes!($e1);)*
         pub static ACE_DEFAULT: &[(&str, &[u8])] = &[$(($e1, $idt),)*];
@@ -20,24 +23,11 @@ macro_rules! default {
 }
 
 pub mod ace;
+pub mod doctor;
+pub mod error;
 pub mod theme;
 
-#[derive(Debug)]
-pub enum Error {
-    StrNotFound,
-    FileNotFound,
-    FileNotCreated,
-    FileNotWritten,
-    DirNotCreated,
-    DirNotRemoved,
-    DirNotRead,
-    AceNotFound,
-    MdbookNotParsed,
-    DeserializedFailed,
-}
-
-pub type Result<T> = std::result::Result<T, Error>;
-// pub mod error;
+pub use error::{Error, Result};
 
 pub struct PreTheme;
 
@@ -51,15 +41,78 @@ pub fn theme_dir(root: &Path, config: &Config) -> PathBuf {
     root.join(theme_dir_)
 }
 
+/// absolute paths in `output.html.additional-css` or `output.html.additional-js` ,
+/// according to `kind` being `css` or `js`
+pub fn additional(root: &Path, config: &Config, kind: &str) -> Vec<PathBuf> {
+    config
+        .get(&format!("output.html.additional-{kind}"))
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|p| p.as_str())
+                .map(|p| root.join(p))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// An empty dir for a test, named after it; what an earlier run left there is removed.
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mdbook-theme-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 impl Preprocessor for PreTheme {
     fn name(&self) -> &str {
         "theme"
     }
 
-    fn run(&self, ctx: &PreprocessorContext, book: Book) -> result::Result<Book, errors::Error> {
+    fn run(
+        &self,
+        ctx: &PreprocessorContext,
+        mut book: Book,
+    ) -> result::Result<Book, errors::Error> {
         let dir = theme_dir(&ctx.root, &ctx.config);
         if let Some(theme) = ctx.config.get_preprocessor(self.name()) {
-            theme::config::run(theme, dir);
+            let key = "output.theme-ace";
+            let ace = ctx
+                .config
+                .get_deserialized_opt::<ace::Ace, _>(key)
+                .map_err(|e| Error::DeserializedFailed {
+                    key: key.into(),
+                    source: Some(e.into()),
+                })?
+                .map(|ace| ace::Ace {
+                    theme_dir: dir.clone(),
+                    ..ace
+                });
+            let language = ctx.config.book.language.as_deref();
+            let pagetoc = theme::config::run(
+                theme,
+                &ctx.root,
+                dir.clone(),
+                ace.as_ref(),
+                language,
+                &ctx.mdbook_version,
+            )?;
+            if let Some(pagetoc) = pagetoc {
+                let html = ctx.config.html_config().unwrap_or_default();
+                pagetoc.chapters(&mut book, html.smart_punctuation())?;
+            }
+            let on = |key: &str| theme.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+            let css = dir.join(theme::overrides::OVERRIDE_CSS);
+            if on("override")
+                && !on("turn-off")
+                && !additional(&ctx.root, &ctx.config, "css").contains(&css)
+            {
+                eprintln!(
+                    "Warning: add \"{}\" to `additional-css` in [output.html] to load the override stylesheet",
+                    css.strip_prefix(&ctx.root).unwrap_or(&css).display()
+                );
+            }
         }
 
         Ok(book)
@@ -0,0 +1,352 @@
+//! Colours in css: parsing `#hex` , `rgb()` / `rgba()` , `hsl()` / `hsla()` and named colours,
+//! checking config values, and the bits of colour math needed to derive a theme from a few colours.
+
+use super::config::similar;
+use std::fmt;
+
+/// keywords accepted as colours, which have no fixed value
+const KEYWORDS: &[&str] = &["currentcolor", "inherit", "initial", "unset", "revert"];
+
+/// An sRGB colour: channels in `0.0..=1.0` .
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgba {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Rgba = Rgba::rgb(1.0, 1.0, 1.0);
+
+    pub const fn rgb(r: f64, g: f64, b: f64) -> Self {
+        Rgba { r, g, b, a: 1.0 }
+    }
+
+    /// Parse a css colour, or tell why it's not one.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let lower = s.to_ascii_lowercase();
+        if let Some(hex) = lower.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(|| format!("`{s}` is not a valid hex colour"));
+        } else if lower == "transparent" {
+            return Ok(Rgba {
+                a: 0.0,
+                ..Rgba::BLACK
+            });
+        } else if let Some((_, rgb)) = NAMED.iter().find(|(name, _)| *name == lower) {
+            let c = |shift: u32| ((rgb >> shift) & 0xff) as f64 / 255.0;
+            return Ok(Rgba::rgb(c(16), c(8), c(0)));
+        }
+        let (name, args) = match lower.find('(') {
+            Some(open) if lower.ends_with(')') => {
+                (&lower[..open], &lower[open + 1..lower.len() - 1])
+            }
+            _ => {
+                let names = NAMED.iter().map(|(name, _)| *name);
+                return Err(match similar(&lower, names).first() {
+                    Some(name) => format!("`{s}` is not a colour, did you mean `{name}`?"),
+                    None => format!("`{s}` is not a colour"),
+                });
+            }
+        };
+        let color = match name.trim() {
+            "rgb" | "rgba" => parse_rgb(args),
+            "hsl" | "hsla" => parse_hsl(args),
+            _ => return Err(format!("`{name}()` is not a supported colour function")),
+        };
+        color.ok_or_else(|| format!("`{s}` has invalid arguments"))
+    }
+
+    /// Mix with `other` : `0.0` is `self` and `1.0` is `other` .
+    pub fn mix(self, other: Rgba, amount: f64) -> Self {
+        let m = |x: f64, y: f64| x + (y - x) * amount;
+        Rgba {
+            r: m(self.r, other.r),
+            g: m(self.g, other.g),
+            b: m(self.b, other.b),
+            a: m(self.a, other.a),
+        }
+    }
+
+    /// WCAG relative luminance
+    pub fn luminance(self) -> f64 {
+        let c = |v: f64| {
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * c(self.r) + 0.7152 * c(self.g) + 0.0722 * c(self.b)
+    }
+
+    /// WCAG contrast ratio, from 1 to 21
+    pub fn contrast(self, other: Rgba) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    pub fn is_dark(self) -> bool {
+        self.contrast(Rgba::WHITE) > self.contrast(Rgba::BLACK)
+    }
+
+    /// Move towards `towards` until the contrast against `bg` reaches `ratio` (if possible).
+    pub fn with_contrast(self, bg: Rgba, ratio: f64, towards: Rgba) -> Self {
+        (0..=20)
+            .map(|i| self.mix(towards, i as f64 / 20.0))
+            .find(|c| c.contrast(bg) >= ratio)
+            .unwrap_or(towards)
+    }
+}
+
+/// Check a colour value from book.toml: a colour, a `var()` or a keyword like `currentcolor` .
+/// It's normalized by trimming and lowercasing.
+pub fn normalize(value: &str) -> Result<String, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    if lower.starts_with("var(") && lower.ends_with(')') {
+        return Ok(value.trim().to_string()); // variable names are case-sensitive
+    } else if !KEYWORDS.contains(&lower.as_str()) {
+        Rgba::parse(&lower)?;
+    }
+    Ok(lower)
+}
+
+/// Whether a value in a stylesheet is a colour: the new value for it has to be one as well.
+pub fn is_color(value: &str) -> bool {
+    normalize(value).is_ok()
+}
+
+/// `#rrggbb` , or `#rrggbbaa` if not opaque
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        write!(f, "#{:02x}{:02x}{:02x}", c(self.r), c(self.g), c(self.b))?;
+        if c(self.a) != 255 {
+            write!(f, "{:02x}", c(self.a))?;
+        }
+        Ok(())
+    }
+}
+
+/// css named colours
+#[rustfmt::skip]
+const NAMED: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff), ("antiquewhite", 0xfaebd7), ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4), ("azure", 0xf0ffff), ("beige", 0xf5f5dc), ("bisque", 0xffe4c4),
+    ("black", 0x000000), ("blanchedalmond", 0xffebcd), ("blue", 0x0000ff), ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a), ("burlywood", 0xdeb887), ("cadetblue", 0x5f9ea0), ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e), ("coral", 0xff7f50), ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc), ("crimson", 0xdc143c), ("cyan", 0x00ffff), ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b), ("darkgoldenrod", 0xb8860b), ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400), ("darkgrey", 0xa9a9a9), ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b), ("darkolivegreen", 0x556b2f), ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc), ("darkred", 0x8b0000), ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f), ("darkslateblue", 0x483d8b), ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f), ("darkturquoise", 0x00ced1), ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493), ("deepskyblue", 0x00bfff), ("dimgray", 0x696969), ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff), ("firebrick", 0xb22222), ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22), ("fuchsia", 0xff00ff), ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff), ("gold", 0xffd700), ("goldenrod", 0xdaa520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xadff2f), ("grey", 0x808080), ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4), ("indianred", 0xcd5c5c), ("indigo", 0x4b0082), ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c), ("lavender", 0xe6e6fa), ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00), ("lemonchiffon", 0xfffacd), ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080), ("lightcyan", 0xe0ffff), ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3), ("lightgreen", 0x90ee90), ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1), ("lightsalmon", 0xffa07a), ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa), ("lightslategray", 0x778899), ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de), ("lightyellow", 0xffffe0), ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32), ("linen", 0xfaf0e6), ("magenta", 0xff00ff), ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa), ("mediumblue", 0x0000cd), ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db), ("mediumseagreen", 0x3cb371), ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a), ("mediumturquoise", 0x48d1cc), ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970), ("mintcream", 0xf5fffa), ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5), ("navajowhite", 0xffdead), ("navy", 0x000080), ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000), ("olivedrab", 0x6b8e23), ("orange", 0xffa500), ("orangered", 0xff4500),
+    ("orchid", 0xda70d6), ("palegoldenrod", 0xeee8aa), ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee), ("palevioletred", 0xdb7093), ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9), ("peru", 0xcd853f), ("pink", 0xffc0cb), ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6), ("purple", 0x800080), ("rebeccapurple", 0x663399), ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f), ("royalblue", 0x4169e1), ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072), ("sandybrown", 0xf4a460), ("seagreen", 0x2e8b57), ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d), ("silver", 0xc0c0c0), ("skyblue", 0x87ceeb), ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xfffafa), ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4), ("tan", 0xd2b48c), ("teal", 0x008080), ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347), ("turquoise", 0x40e0d0), ("violet", 0xee82ee), ("wheat", 0xf5deb3),
+    ("white", 0xffffff), ("whitesmoke", 0xf5f5f5), ("yellow", 0xffff00), ("yellowgreen", 0x9acd32),
+];
+
+fn parse_hex(hex: &str) -> Option<Rgba> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<f64> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| (d * 17) as f64))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(f64::from))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    let a = digits.get(3).map_or(1.0, |a| a / 255.0);
+    Some(Rgba {
+        r: digits[0] / 255.0,
+        g: digits[1] / 255.0,
+        b: digits[2] / 255.0,
+        a,
+    })
+}
+
+/// Split `a, b, c, d` or `a b c / d` into 3 or 4 arguments: in the space syntax, the alpha only
+/// comes after `/` .
+fn args(args: &str) -> Option<Vec<&str>> {
+    let v: Vec<_> = if args.contains(',') {
+        args.split(',').map(str::trim).collect()
+    } else {
+        let (channels, alpha) = match args.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha.trim())),
+            None => (args, None),
+        };
+        let mut v: Vec<_> = channels.split_whitespace().collect();
+        if v.len() != 3 || alpha.is_some_and(|a| a.is_empty() || a.contains(char::is_whitespace)) {
+            return None;
+        }
+        v.extend(alpha);
+        v
+    };
+    if v.len() == 3 || v.len() == 4 {
+        Some(v)
+    } else {
+        None
+    }
+}
+
+/// `50%` is `0.5 * max` , and a plain number is as it is
+fn number(s: &str, max: f64) -> Option<f64> {
+    let v = match s.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f64>().ok()? / 100.0 * max,
+        None => s.parse().ok()?,
+    };
+    if v.is_finite() {
+        Some(v)
+    } else {
+        None
+    }
+}
+
+fn alpha(v: &[&str]) -> Option<f64> {
+    v.get(3)
+        .map_or(Some(1.0), |a| number(a, 1.0))
+        .map(|a| a.clamp(0.0, 1.0))
+}
+
+fn parse_rgb(s: &str) -> Option<Rgba> {
+    let v = args(s)?;
+    let c = |s: &str| number(s, 255.0).map(|x| x.clamp(0.0, 255.0) / 255.0);
+    Some(Rgba {
+        r: c(v[0])?,
+        g: c(v[1])?,
+        b: c(v[2])?,
+        a: alpha(&v)?,
+    })
+}
+
+fn parse_hsl(s: &str) -> Option<Rgba> {
+    let v = args(s)?;
+    let h = v[0]
+        .strip_suffix("deg")
+        .unwrap_or(v[0])
+        .parse::<f64>()
+        .ok()?;
+    let percent = |s: &str| number(s.strip_suffix('%')?, 1.0).map(|x| (x / 100.0).clamp(0.0, 1.0));
+    let (s, l) = (percent(v[1])?, percent(v[2])?);
+    let f = |n: f64| {
+        let k = (n + h.rem_euclid(360.0) / 30.0) % 12.0;
+        l - s * l.min(1.0 - l) * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Some(Rgba {
+        r: f(0.0),
+        g: f(8.0),
+        b: f(4.0),
+        a: alpha(&v)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, Rgba};
+
+    fn hex(s: &str) -> String {
+        Rgba::parse(s).unwrap().to_string()
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert_eq!(hex("#abc"), "#aabbcc");
+        assert_eq!(hex("#abc8"), "#aabbcc88");
+        assert_eq!(hex("#1F1FFF"), "#1f1fff");
+        assert_eq!(hex("#1f1fff80"), "#1f1fff80");
+        assert_eq!(hex("#1f1fffff"), "#1f1fff");
+        for bad in ["#1f1fffz", "#12", "#12345", "#1234567", "#"] {
+            assert!(Rgba::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn rgb_colours() {
+        assert_eq!(hex("rgb(255, 0, 128)"), "#ff0080");
+        assert_eq!(hex("rgba(255, 0, 128, 0.5)"), "#ff008080");
+        assert_eq!(hex("rgb(255 0 128)"), "#ff0080");
+        assert_eq!(hex("rgb(100% 0% 50% / 50%)"), "#ff008080");
+        assert_eq!(hex("rgb(255 0 128/0.5)"), "#ff008080");
+        for bad in [
+            "rgb(1 2 3 4)",
+            "rgb(1 2 / 3)",
+            "rgb(1 2 3 /)",
+            "rgb(1 2 3 / 4 5)",
+            "rgb(1, 2)",
+            "rgb(1, 2, x)",
+        ] {
+            assert!(Rgba::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn hsl_colours() {
+        assert_eq!(hex("hsl(0, 100%, 50%)"), "#ff0000");
+        assert_eq!(hex("hsla(120deg, 100%, 25%, 0.5)"), "#00800080");
+        assert_eq!(hex("hsl(240 100% 50%)"), "#0000ff");
+        assert_eq!(hex("hsl(-120 100% 50% / 1)"), "#0000ff");
+        assert!(Rgba::parse("hsl(0 100% 50% 1)").is_err());
+        assert!(Rgba::parse("hsl(0, 100, 50)").is_err());
+    }
+
+    #[test]
+    fn names_and_keywords() {
+        assert_eq!(hex("RebeccaPurple"), "#663399");
+        assert_eq!(hex("transparent"), "#00000000");
+        assert_eq!(
+            Rgba::parse("bleu").unwrap_err(),
+            "`bleu` is not a colour, did you mean `blue`?"
+        );
+        assert!(Rgba::parse("lab(50% 0 0)").is_err());
+        assert_eq!(normalize(" CurrentColor ").unwrap(), "currentcolor");
+        assert_eq!(normalize("var(--Links)").unwrap(), "var(--Links)");
+        assert!(normalize("bleu").is_err());
+    }
+
+    #[test]
+    fn contrast_ratios() {
+        assert!((Rgba::BLACK.contrast(Rgba::WHITE) - 21.0).abs() < 1e-9);
+        assert!((Rgba::WHITE.contrast(Rgba::WHITE) - 1.0).abs() < 1e-9);
+        let grey = Rgba::parse("#767676").unwrap();
+        assert!((grey.contrast(Rgba::WHITE) - 4.54).abs() < 0.01);
+        assert!(Rgba::parse("#333").unwrap().is_dark());
+        assert!(!Rgba::parse("#ddd").unwrap().is_dark());
+    }
+}
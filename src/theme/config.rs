@@ -1,42 +1,861 @@
-use super::{default::DEFAULT, CssFile, CssFile::Invalid, Item, Ready, Theme, Value};
-use std::{collections::HashMap, path::PathBuf};
+use super::{
+    color::Rgba,
+    contrast,
+    convert::css_value,
+    default::{BUILTIN_THEMES, DEFAULT, MDBOOK_KEYS, OPTIONS},
+    fonts::{Font, Fonts},
+    layout::Layout,
+    output::Output,
+    pagetoc::{self, Pagetoc, Position},
+    palette::{self, Palette, SEEDS},
+    strings::{self, Strings},
+    units::{self, ValueType},
+    Color, CssFile, CustomTheme, Declare, Declares, Item, Ready, Theme, Value,
+};
+use crate::{ace::Ace, Error, Result};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 use toml::{map::Map, value::Value as MdValue};
 
-pub fn run(input: &Map<String, MdValue>, dir: PathBuf) {
+/// `root` is the book root, `dir` the theme dir, and `ace` is `output.theme-ace` if set, whose
+/// themes are checked by `contrast` as well.
+///
+/// The pagetoc options are returned when the pagetoc is on, for the chapters to be processed.
+/// `language` is `book.language` , choosing the labels put into the book, and `mdbook_version`
+/// the version of the running mdBook, which the layout of its theme files is detected by.
+pub fn run(
+    input: &Map<String, MdValue>,
+    root: &Path,
+    dir: PathBuf,
+    ace: Option<&Ace>,
+    language: Option<&str>,
+    mdbook_version: &str,
+) -> Result<Option<Pagetoc>> {
     let mut input = input.to_owned();
     if input
         .remove("turn-off")
         .is_some_and(|p| p.as_bool().unwrap_or(false))
     {
-        return;
+        return Ok(None);
     }
+    let layout = Layout::detect(mdbook_version)?;
 
-    Theme::create_theme_dirs(dir.clone()).unwrap_or_default(); // create all dirs just once
+    let strict = input
+        .remove("strict")
+        .is_some_and(|p| p.as_bool().unwrap_or(false));
+    let pagetoc = input.remove("pagetoc"); // `pagetoc = true` or `[preprocessor.theme.pagetoc]`
+    let pagetoc = pagetoc.as_ref().map_or(Ok(None), pagetoc_from)?;
+    let strings = input.remove("strings"); // `[preprocessor.theme.strings]`
+    let pagetoc = match pagetoc {
+        Some(p) => Some(Pagetoc {
+            strings: strings_from(strings.as_ref(), language.unwrap_or("en"))?,
+            layout,
+            ..p
+        }),
+        None => None,
+    };
+    let px_to_rem = input
+        .remove("px-to-rem")
+        .is_some_and(|p| p.as_bool().unwrap_or(false));
+    let dry_run = input
+        .remove("dry-run")
+        .is_some_and(|p| p.as_bool().unwrap_or(false));
+    let override_mode = input
+        .remove("override")
+        .is_some_and(|p| p.as_bool().unwrap_or(false));
+    let ratio =
+        contrast::ratio(input.remove("contrast").as_ref()).map_err(|e| invalid("contrast", &e))?;
+    let contrast_strict = input
+        .remove("contrast-strict")
+        .is_some_and(|p| p.as_bool().unwrap_or(false));
+    for key in MDBOOK_KEYS {
+        input.remove(*key);
+    }
+    check_items(&input, strict)?;
 
-    if input
-        .remove("pagetoc")
-        .is_some_and(|p| p.as_bool().unwrap_or(false))
-    {
-        Theme::from(CssFile::Pagetoc, Ready::default(), dir.clone()).pagetoc(); // pagetoc defaults
+    // non-string values are converted into css values in place, before being borrowed;
+    // then values are checked against the types of items
+    let root_px = if px_to_rem {
+        Some(root_font_size(&input, pagetoc.is_some())?)
+    } else {
+        None
+    };
+    for (item, value) in input.iter_mut() {
+        if let Some((_, _, _, ty)) = DEFAULT.iter().find(|(_, i, _, _)| i.get() == item) {
+            let v = css_value(item, value).map_err(|e| invalid(item, &e))?;
+            let root_px = root_px.filter(|_| item != "root-font-size");
+            let v = units::check(*ty, &v, root_px).map_err(|e| invalid(item, &e))?;
+            *value = MdValue::String(v);
+        }
     }
+    let mut css = input
+        .remove("css")
+        .unwrap_or_else(|| MdValue::Table(Map::new()));
+    convert_declares(&mut css, "css")?;
+    let mut declares = HashMap::new(); // `[preprocessor.theme.css]` configs
+    if let Some(files) = css.as_table() {
+        declares_from(files, &mut declares)?;
+    }
+    let colors = input.remove("colors"); // `[preprocessor.theme.colors]` configs
+    let mut colors = colors.as_ref().map_or(Ok(Vec::new()), colors_from)?;
+    let custom_themes = input.remove("custom-themes"); // `[preprocessor.theme.custom-themes]`
+    let custom_themes = (custom_themes.as_ref()).map_or(Ok(Vec::new()), custom_themes_from)?;
+    let palette = input.remove("palette"); // `[preprocessor.theme.palette]`
+    let mut palette = palette.as_ref().map(palette_from).transpose()?;
+    let fonts = input.remove("fonts"); // `[preprocessor.theme.fonts]`
+    let fonts = fonts.map(|f| fonts_from(&f, root)).transpose()?;
 
-    let default_map: HashMap<_, _> = DEFAULT.iter().map(|(css, item, _)| (*item, *css)).collect();
+    let default_map: HashMap<_, _> = DEFAULT
+        .iter()
+        .map(|(css, item, _, _)| (*item, *css))
+        .collect();
     let mut config = HashMap::new(); // ultimate theme configs
 
-    input
+    for (item, value) in input.iter() {
+        let item = item.as_str();
+        let css = match default_map.get(item) {
+            Some(css) => *css,
+            None => continue, // options and unknown items are handled above
+        };
+        let value = value
+            .as_str()
+            .ok_or_else(|| invalid(item, "expected a string"))?;
+        (*config.entry(css).or_insert_with(Vec::new)).push((Item(item), Value(value)));
+    }
+
+    if !colors.is_empty() || !custom_themes.is_empty() || palette.is_some() {
+        config.entry(CssFile::Variables).or_insert_with(Vec::new);
+    }
+    if !custom_themes.is_empty() {
+        config.entry(CssFile::Index).or_insert_with(Vec::new);
+    }
+
+    // colours set or derived by the config, whose contrast is checked
+    let mut configured = Vec::new();
+    for (Item(item), _) in config.get(&CssFile::Variables).into_iter().flatten() {
+        if let Some((theme, name)) = item.split_once('-') {
+            configured.push((theme, name));
+        }
+    }
+    configured.extend(colors.iter().map(|c| (c.theme, c.name)));
+    for t in &custom_themes {
+        configured.extend(t.colors.iter().map(|(name, _)| (t.name, *name)));
+    }
+    if let Some(p) = &palette {
+        configured.extend(p.colors.iter().map(|(name, _)| (p.theme, *name)));
+    }
+
+    // files are staged in `output` , and only written once all of them are processed and checked
+    let output = Rc::new(RefCell::new(
+        Output::new(&dir, dry_run)?.override_mode(override_mode),
+    ));
+
+    // the options every theme file is processed with: one place so that no file misses them
+    let theme = |css, ready, declares| {
+        Theme::from(css, ready, dir.clone())
+            .declares(declares)
+            .pagetoc_options(pagetoc.clone().unwrap_or_default())
+            .layout(layout)
+            .strict(strict)
+            .output(output.clone())
+    };
+
+    if pagetoc.is_some() {
+        // pagetoc defaults: not set by the user, so skipped if a selector is missing, even strict
+        theme(CssFile::Pagetoc, Ready::default(), Declares::default())
+            .strict(false)
+            .pagetoc()?;
+    }
+
+    config.into_iter().try_for_each(|(css, ready)| {
+        let declares = declares.remove(&css).unwrap_or_default();
+        let colors = match css {
+            CssFile::Variables => std::mem::take(&mut colors),
+            _ => Vec::new(),
+        };
+        let palette = match css {
+            CssFile::Variables => palette.take(),
+            _ => None,
+        };
+        let custom_themes = match css {
+            CssFile::Variables | CssFile::Index => custom_themes.clone(),
+            _ => Vec::new(),
+        };
+        theme(css, Ready(ready), declares)
+            .colors(colors)
+            .custom_themes(custom_themes)
+            .palette(palette)
+            .process()
+            .map(drop)
+    })?;
+
+    declares.into_iter().try_for_each(|(css, declares)| {
+        theme(css, Ready::default(), declares).process().map(drop)
+    })?;
+    if let Some(fonts) = fonts {
+        fonts.write(&dir, &mut output.borrow_mut())?;
+    }
+    output.borrow_mut().write_overrides()?;
+
+    if let Some(ratio) = ratio {
+        let mut low = contrast::themes(&dir, &output.borrow(), ratio, &configured)?;
+        if let Some(ace) = ace {
+            low.extend(contrast::ace(ace, ratio));
+        }
+        check_contrast(low, contrast_strict)?;
+    }
+
+    output.borrow_mut().commit()?;
+    if dry_run {
+        // stdout is for the book passed back to mdbook
+        let diffs = output.borrow().diffs();
+        if diffs.is_empty() {
+            eprintln!("mdbook-theme (dry-run): no theme file would change");
+        } else {
+            eprint!("{diffs}");
+        }
+    }
+    Ok(pagetoc)
+}
+
+/// Report items unknown to this tool: warn about them, or fail in strict mode.
+fn check_items(input: &Map<String, MdValue>, strict: bool) -> Result<()> {
+    let unknown: Vec<_> = input
+        .keys()
+        .filter(|item| !OPTIONS.contains(&item.as_str()))
+        .filter(|item| DEFAULT.iter().all(|(_, i, _, _)| i.get() != item.as_str()))
+        .map(|item| diagnose(item))
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else if strict {
+        Err(Error::UnknownItems(unknown))
+    } else {
+        for u in unknown {
+            eprintln!("Warning: unknown item in [preprocessor.theme]: {u}");
+        }
+        Ok(())
+    }
+}
+
+/// Report colours below the contrast ratio: warn about them, or fail with `contrast-strict` ,
+/// which is apart from `strict` so that a colour choice doesn't fail a build checked for typos.
+fn check_contrast(low: Vec<String>, strict: bool) -> Result<()> {
+    if low.is_empty() {
+        Ok(())
+    } else if strict {
+        Err(Error::LowContrast(low))
+    } else {
+        for l in low {
+            eprintln!("Warning: low contrast: {l}");
+        }
+        Ok(())
+    }
+}
+
+/// `item` with the closest known items and the file each one targets.
+fn diagnose(item: &str) -> String {
+    let known = DEFAULT
         .iter()
-        .map(|(item, value)| {
-            let item = item.as_str();
-            (*config
-                .entry(default_map.get(item).unwrap_or(&Invalid))
-                .or_insert_with(Vec::new))
-            .push((Item(item), Value(value.as_str().unwrap())))
+        .map(|(css, i, _, _)| (i.get(), format!(" ({})", css.filename())))
+        .chain(OPTIONS.iter().map(|o| (*o, String::new())));
+    let files: HashMap<_, _> = known.collect();
+    let similar: Vec<_> = similar(item, files.keys().copied())
+        .iter()
+        .map(|k| format!("`{k}`{}", files[k.as_str()]))
+        .collect();
+    if similar.is_empty() {
+        format!("`{item}`")
+    } else {
+        format!("`{item}`, did you mean {}?", similar.join(" or "))
+    }
+}
+
+/// At most 3 words in `known` similar to `word` , the closest first.
+pub fn similar<'k>(word: &str, known: impl Iterator<Item = &'k str>) -> Vec<String> {
+    let mut similar: Vec<_> = known
+        .map(|k| (strsim::jaro_winkler(word, k), k))
+        .filter(|(score, _)| *score > 0.8)
+        .collect();
+    similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+    similar.dedup_by_key(|(_, k)| *k);
+    similar.iter().take(3).map(|(_, k)| k.to_string()).collect()
+}
+
+/// The root font size in px for `px-to-rem` : `root-font-size` in book.toml, or the one set by
+/// `pagetoc = true` , or mdBook's `62.5%` .
+fn root_font_size(input: &Map<String, MdValue>, pagetoc: bool) -> Result<f64> {
+    let item = "root-font-size";
+    let value = match input.get(item) {
+        Some(v) => css_value(item, v).map_err(|e| invalid(item, &e))?,
+        None if pagetoc => String::from("70%"),
+        None => String::from("62.5%"),
+    };
+    let value = units::check(ValueType::FontSize, &value, None).map_err(|e| invalid(item, &e))?;
+    units::font_size_px(&value).ok_or_else(|| {
+        let reason = format!("`{value}` can't be converted to px for `px-to-rem`");
+        invalid(item, &reason)
+    })
+}
+
+fn invalid(item: &str, reason: &str) -> Error {
+    Error::InvalidValue {
+        item: item.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Collect declarations from `[preprocessor.theme.css."file"."selector"]` and
+/// `[preprocessor.theme.css."file"."@media ..."."selector"]` . Only css files are accepted.
+fn declares_from<'a>(
+    files: &'a Map<String, MdValue>,
+    declares: &mut HashMap<CssFile<'a>, Declares<'a>>,
+) -> Result<()> {
+    for (file, rules) in files {
+        let css = CssFile::variant(file);
+        let key = format!("css.{file}");
+        if !css.is_css() {
+            return Err(invalid(&key, "only css files can be set"));
+        }
+        let rules = rules
+            .as_table()
+            .ok_or_else(|| invalid(&key, "expected a table of selectors"))?;
+        let declares = declares.entry(css).or_default();
+        for (selector, properties) in rules {
+            let key = format!("{key}.{selector}");
+            let properties = properties
+                .as_table()
+                .ok_or_else(|| invalid(&key, "expected a table of properties"))?;
+            if selector.starts_with('@') {
+                for (inner, properties) in properties {
+                    let key = format!("{key}.{inner}");
+                    let properties = properties
+                        .as_table()
+                        .ok_or_else(|| invalid(&key, "expected a table of properties"))?;
+                    push_declares(declares, Some(selector), inner, properties, &key)?;
+                }
+            } else {
+                push_declares(declares, None, selector, properties, &key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collect colour variables from `[preprocessor.theme.colors."theme"]` .
+fn colors_from(themes: &MdValue) -> Result<Vec<Color<'_>>> {
+    let themes = themes
+        .as_table()
+        .ok_or_else(|| invalid("colors", "expected a table of themes"))?;
+    let mut colors = Vec::new();
+    for (theme, variables) in themes {
+        let key = format!("colors.{theme}");
+        let variables = variables
+            .as_table()
+            .ok_or_else(|| invalid(&key, "expected a table of colours"))?;
+        for (name, value) in variables {
+            let value = value
+                .as_str()
+                .ok_or_else(|| invalid(&format!("{key}.{name}"), "expected a string"))?;
+            colors.push(Color {
+                theme,
+                name,
+                value: Value(value),
+            });
+        }
+    }
+    Ok(colors)
+}
+
+/// Collect new themes from `[preprocessor.theme.custom-themes."name"]` : `base` (`light` by
+/// default) and `label` (the capitalized name by default) are options, and other keys are
+/// variables to change.
+fn custom_themes_from(themes: &MdValue) -> Result<Vec<CustomTheme<'_>>> {
+    let themes = themes
+        .as_table()
+        .ok_or_else(|| invalid("custom-themes", "expected a table of themes"))?;
+    let mut custom_themes = Vec::new();
+    for (name, options) in themes {
+        let key = format!("custom-themes.{name}");
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(valid)
+        {
+            let reason = "a theme name can only have letters, digits, `-` and `_`";
+            return Err(invalid(&key, reason));
+        } else if BUILTIN_THEMES.contains(&name.as_str()) {
+            let reason = format!("`{name}` is built-in: use [preprocessor.theme.colors.{name}]");
+            return Err(invalid(&key, &reason));
+        }
+        let options = options
+            .as_table()
+            .ok_or_else(|| invalid(&key, "expected a table of options and colours"))?;
+        let (mut base, mut label, mut colors) = ("light", None, Vec::new());
+        for (k, v) in options {
+            let v = v
+                .as_str()
+                .ok_or_else(|| invalid(&format!("{key}.{k}"), "expected a string"))?;
+            match k.as_str() {
+                "base" => base = v,
+                "label" => label = Some(v.to_string()),
+                _ => colors.push((k.as_str(), Value(v))),
+            }
+        }
+        let mut chars = name.chars();
+        let capitalized = chars
+            .next()
+            .map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect());
+        custom_themes.push(CustomTheme {
+            name,
+            label: label.unwrap_or(capitalized),
+            base,
+            colors,
+        });
+    }
+    Ok(custom_themes)
+}
+
+/// Derive colours from `[preprocessor.theme.palette]` for `theme` (`light` by default).
+fn palette_from(seeds: &MdValue) -> Result<Palette<'_>> {
+    let seeds = seeds
+        .as_table()
+        .ok_or_else(|| invalid("palette", "expected a table of colours"))?;
+    let mut theme = "light";
+    let mut colors = HashMap::new();
+    for (k, v) in seeds {
+        let key = format!("palette.{k}");
+        let v = v
+            .as_str()
+            .ok_or_else(|| invalid(&key, "expected a string"))?;
+        if k == "theme" {
+            theme = v;
+        } else if SEEDS.contains(&k.as_str()) {
+            colors.insert(k.as_str(), Rgba::parse(v).map_err(|e| invalid(&key, &e))?);
+        } else {
+            let known = SEEDS.iter().copied().chain(std::iter::once("theme"));
+            let reason = match similar(k, known).first() {
+                Some(s) => format!("unknown seed, did you mean `{s}`?"),
+                None => format!("unknown seed, expected one of {}", SEEDS.join(", ")),
+            };
+            return Err(invalid(&key, &reason));
+        }
+    }
+    let seed = |k: &str| {
+        (colors.get(k).copied()).ok_or_else(|| {
+            invalid(
+                &format!("palette.{k}"),
+                "missing, while `background`, `foreground` and `accent` are required",
+            )
         })
-        .last();
+    };
+    let (bg, fg, accent) = (seed("background")?, seed("foreground")?, seed("accent")?);
+    let colors = palette::derive(bg, fg, accent, colors.get("secondary").copied());
+    Ok(Palette { theme, colors })
+}
+
+/// `pagetoc = true` for the default options, or a table of options.
+pub fn pagetoc_from(value: &MdValue) -> Result<Option<Pagetoc>> {
+    let table = match value {
+        MdValue::Boolean(on) => return Ok(on.then(Pagetoc::default)),
+        MdValue::Table(table) => table,
+        _ => return Err(invalid("pagetoc", "expected true, false or a table")),
+    };
+    let mut options = Pagetoc::default();
+    for (k, v) in table {
+        let key = format!("pagetoc.{k}");
+        let level = || match v.as_integer() {
+            Some(n @ 1..=6) => Ok(n as u8),
+            _ => Err(invalid(&key, "expected a heading level from 1 to 6")),
+        };
+        let boolean = || {
+            v.as_bool()
+                .ok_or_else(|| invalid(&key, "expected true or false"))
+        };
+        let length = || {
+            let value = css_value(k, v).map_err(|e| invalid(&key, &e))?;
+            units::check(ValueType::Length, &value, None).map_err(|e| invalid(&key, &e))
+        };
+        match k.as_str() {
+            "min-level" => options.min_level = level()?,
+            "max-level" => options.max_level = level()?,
+            "indent" => options.indent = length()?,
+            "breakpoint" => options.breakpoint = length()?,
+            "sticky-offset" => options.sticky_offset = length()?,
+            "position" => match v.as_str().and_then(Position::from) {
+                Some(position) => options.position = position,
+                None => return Err(invalid(&key, "expected \"right\", \"left\" or \"inline\"")),
+            },
+            "static" => options.static_toc = boolean()?,
+            "smooth-scroll" => options.smooth_scroll = boolean()?,
+            "collapsible" => options.collapsible = boolean()?,
+            "mobile" => options.mobile = boolean()?,
+            "exclude" => {
+                let patterns = v.as_array().map(|a| a.iter().map(|p| p.as_str()).collect());
+                let patterns: Option<Vec<_>> =
+                    patterns.and_then(|p: Vec<_>| p.into_iter().collect());
+                let patterns = patterns
+                    .ok_or_else(|| invalid(&key, "expected an array of chapter paths or globs"))?;
+                for p in &patterns {
+                    globset::Glob::new(p).map_err(|e| invalid(&key, &e.to_string()))?;
+                }
+                options.exclude = patterns.into_iter().map(String::from).collect();
+            }
+            "selector" => match v.as_str() {
+                Some(s) if !s.trim().is_empty() => options.selector = s.trim().to_string(),
+                _ => return Err(invalid(&key, "expected a css selector")),
+            },
+            _ => {
+                let reason = match similar(k, pagetoc::KEYS.iter().copied()).first() {
+                    Some(s) => format!("unknown key, did you mean `{s}`?"),
+                    None => format!("unknown key, expected one of {}", pagetoc::KEYS.join(", ")),
+                };
+                return Err(invalid(&key, &reason));
+            }
+        }
+    }
+    if options.min_level > options.max_level {
+        let reason = format!(
+            "`min-level = {}` is greater than `max-level = {}`",
+            options.min_level, options.max_level
+        );
+        return Err(invalid("pagetoc", &reason));
+    }
+    Ok(Some(options))
+}
+
+/// The built-in labels for `language` , overridden by `value` ; warn about the ones left in
+/// English when `language` has no built-in labels.
+fn strings_from(value: Option<&MdValue>, language: &str) -> Result<Strings> {
+    let (labels, english) = labels_from(value, language)?;
+    if !english.is_empty() {
+        eprintln!(
+            "Warning: no built-in labels for `book.language = \"{language}\"`, so English ones \
+             are used for {}; set them in [preprocessor.theme.strings]",
+            english.join(", ")
+        );
+    }
+    Ok(labels)
+}
+
+/// The labels, and the keys falling back to English.
+fn labels_from(value: Option<&MdValue>, language: &str) -> Result<(Strings, Vec<&'static str>)> {
+    let builtin = Strings::builtin(language);
+    let mut labels = builtin.clone().unwrap_or_default();
+    let table = match value {
+        None => None,
+        Some(MdValue::Table(table)) => Some(table),
+        Some(_) => return Err(invalid("strings", "expected a table")),
+    };
+    for (k, v) in table.into_iter().flatten() {
+        let key = format!("strings.{k}");
+        let v = v
+            .as_str()
+            .ok_or_else(|| invalid(&key, "expected a string"))?;
+        if !labels.set(k, v) {
+            let reason = match similar(k, strings::KEYS.iter().copied()).first() {
+                Some(s) => format!("unknown key, did you mean `{s}`?"),
+                None => format!("unknown key, expected one of {}", strings::KEYS.join(", ")),
+            };
+            return Err(invalid(&key, &reason));
+        }
+    }
+    let english = match builtin {
+        Some(_) => Vec::new(),
+        None => (strings::KEYS.iter().copied())
+            .filter(|k| !table.is_some_and(|t| t.contains_key(*k)))
+            .collect(),
+    };
+    Ok((labels, english))
+}
+
+/// `files` are paths relative to the book root, or tables with `path` and optional `family` ,
+/// `weight` and `style` overriding the ones guessed from the file name.
+fn fonts_from(value: &MdValue, root: &Path) -> Result<Fonts> {
+    const KEYS: &[&str] = &["files", "mode"];
+    let table = value
+        .as_table()
+        .ok_or_else(|| invalid("fonts", "expected a table"))?;
+    let mut fonts = Fonts::default();
+    for (k, v) in table {
+        let key = format!("fonts.{k}");
+        match (k.as_str(), v) {
+            ("mode", MdValue::String(m)) if m == "extend" || m == "replace" => {
+                fonts.replace = m == "replace";
+            }
+            ("mode", _) => return Err(invalid(&key, "expected \"extend\" or \"replace\"")),
+            ("files", MdValue::Array(files)) => {
+                for (i, f) in files.iter().enumerate() {
+                    let key = format!("{key}[{i}]");
+                    fonts
+                        .files
+                        .push(font_from(f, root).map_err(|e| invalid(&key, &e))?);
+                }
+            }
+            ("files", _) => return Err(invalid(&key, "expected an array of paths")),
+            _ => {
+                let reason = match similar(k, KEYS.iter().copied()).first() {
+                    Some(s) => format!("unknown key, did you mean `{s}`?"),
+                    None => format!("unknown key, expected one of {}", KEYS.join(", ")),
+                };
+                return Err(invalid(&key, &reason));
+            }
+        }
+    }
+    Ok(fonts)
+}
 
-    config
-        .into_iter()
-        .filter(|(css, _)| **css != Invalid) // exlude use's invalid configs
-        .map(|(css, ready)| Theme::from(*css, Ready(ready), dir.clone()).process())
-        .last();
+fn font_from(value: &MdValue, root: &Path) -> std::result::Result<Font, String> {
+    let (path, table) = match value {
+        MdValue::String(path) => (path.as_str(), None),
+        MdValue::Table(t) => match t.get("path").and_then(|p| p.as_str()) {
+            Some(path) => (path, Some(t)),
+            None => return Err("expected `path` in the table".into()),
+        },
+        _ => return Err("expected a path or a table with `path`".into()),
+    };
+    let path = root.join(path);
+    if !path.is_file() {
+        return Err(format!("`{}` is not found", path.display()));
+    }
+    let mut font = Font::from_path(path)?;
+    for (k, v) in table.into_iter().flatten() {
+        let v = match v {
+            MdValue::String(s) => s.clone(),
+            MdValue::Integer(n) => n.to_string(),
+            _ => return Err(format!("expected a string for `{k}`")),
+        };
+        match k.as_str() {
+            "path" => (),
+            "family" => font.family = v,
+            "weight" => font.weight = v,
+            "style" => font.style = v,
+            _ => {
+                return Err(format!(
+                    "unknown key `{k}`, expected path, family, weight or style"
+                ))
+            }
+        }
+    }
+    Ok(font)
+}
+
+/// Convert non-string values of properties in `[preprocessor.theme.css]` into css values.
+fn convert_declares(value: &mut MdValue, key: &str) -> Result<()> {
+    if let MdValue::Table(table) = value {
+        for (k, v) in table.iter_mut() {
+            let key = format!("{key}.{k}");
+            if v.is_table() {
+                convert_declares(v, &key)?;
+            } else {
+                *v = MdValue::String(css_value(k, v).map_err(|e| invalid(&key, &e))?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn push_declares<'a>(
+    declares: &mut Declares<'a>,
+    at_rule: Option<&'a str>,
+    selector: &'a str,
+    properties: &'a Map<String, MdValue>,
+    key: &str,
+) -> Result<()> {
+    for (property, value) in properties {
+        let value = value
+            .as_str()
+            .ok_or_else(|| invalid(&format!("{key}.{property}"), "expected a string"))?;
+        declares.push(Declare {
+            at_rule,
+            selector,
+            property,
+            value: Value(value),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnose, labels_from, run, similar};
+    use crate::test_dir;
+    use std::{fs, path::Path};
+
+    fn run_toml(input: &str, root: &Path) -> crate::Result<()> {
+        let input = toml::from_str(input).unwrap();
+        let dir = root.join("theme");
+        run(&input, root, dir, None, None, mdbook::MDBOOK_VERSION).map(drop)
+    }
+
+    fn read(root: &Path, file: &str) -> String {
+        fs::read_to_string(root.join("theme").join(file)).unwrap()
+    }
+
+    #[test]
+    fn similar_words_closest_first() {
+        let known = [
+            "sidebar-width",
+            "pagetoc-width",
+            "page-padding",
+            "sidebar-font-size",
+        ];
+        let found = similar("sidebar-widht", known.iter().copied());
+        assert_eq!(found.first().map(String::as_str), Some("sidebar-width"));
+        assert!(!found.contains(&"page-padding".to_string()), "{:?}", found);
+
+        let known = ["a-1", "a-2", "a-3", "a-4", "a-5"];
+        assert_eq!(similar("a-0", known.iter().copied()).len(), 3);
+        assert!(similar("zzz", known.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn diagnose_near_misses_only() {
+        let light = diagnose("light-link");
+        assert!(
+            light.starts_with("`light-link`, did you mean `light-links` (css/variables.css)"),
+            "{}",
+            light
+        );
+        assert!(diagnose("pagetok").starts_with("`pagetok`, did you mean `pagetoc`"));
+        assert_eq!(diagnose("favicon"), "`favicon`");
+        assert_eq!(diagnose("search-bar"), "`search-bar`");
+    }
+
+    #[test]
+    fn pagetoc_options_reach_a_declared_pagetoc_css() {
+        let input = r#"
+            pagetoc = { breakpoint = "1000px", indent = "2em" }
+            [css."pagetoc.css".".pagetoc a"]
+            color = "red"
+        "#;
+        let root = test_dir("pagetoc-options");
+        run_toml(input, &root).unwrap();
+
+        let pagetoc = read(&root, "pagetoc.css");
+        let variables = read(&root, "css/variables.css");
+        fs::remove_dir_all(&root).unwrap();
+        assert!(pagetoc.contains("(max-width:999px)"), "{}", pagetoc);
+        assert!(pagetoc.contains("(min-width:1000px)"), "{}", pagetoc);
+        assert!(pagetoc.contains("* 2em)"), "{}", pagetoc);
+        assert!(pagetoc.contains("color: red"), "{}", pagetoc);
+        assert!(variables.contains("(max-width:999px)"), "{}", variables);
+    }
+
+    #[test]
+    fn nothing_is_written_if_a_color_is_unknown() {
+        let input = r##"
+            pagetoc = true
+            fonts = { mode = "replace" }
+            [colors.light]
+            links = "#000000"
+            no-such-variable = "#000000"
+        "##;
+        let root = test_dir("unknown-color");
+        let result = run_toml(input, &root);
+        let written = root.join("theme").exists();
+        fs::remove_dir_all(&root).unwrap();
+        let item = match &result {
+            Err(crate::Error::Config { item, .. }) => item.as_str(),
+            _ => panic!("{:?}", result),
+        };
+        assert_eq!(item, "colors.light.no-such-variable");
+        assert!(!written);
+    }
+
+    #[test]
+    fn missing_selectors_fail_only_items_set_in_strict_mode() {
+        let run_without_rust = |input: &str| {
+            let root = test_dir("missing-selector");
+            let css = root.join("theme/css");
+            fs::create_dir_all(&css).unwrap();
+            let variables = String::from_utf8_lossy(mdbook::theme::VARIABLES_CSS);
+            fs::write(
+                css.join("variables.css"),
+                variables.replace(".rust {", ".rusty {"),
+            )
+            .unwrap();
+            let result = run_toml(input, &root);
+            fs::remove_dir_all(&root).unwrap();
+            result
+        };
+        assert!(run_without_rust("pagetoc = true\nstrict = true").is_ok());
+        assert!(run_without_rust("rust-links = \"#123456\"").is_ok());
+        let result = run_without_rust("strict = true\nrust-links = \"#123456\"");
+        assert!(
+            matches!(&result, Err(crate::Error::Config { item, .. }) if item == "rust-links"),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn low_contrast_fails_only_with_contrast_strict() {
+        let root = test_dir("contrast-strict");
+        let result = run_toml("strict = true\nlight-links = \"#eeeeee\"", &root);
+        assert!(result.is_ok(), "{:?}", result);
+        fs::remove_dir_all(root.join("theme")).unwrap();
+
+        let result = run_toml("contrast-strict = true\nlight-links = \"#eeeeee\"", &root);
+        let written = root.join("theme").exists();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(
+            matches!(result, Err(crate::Error::LowContrast(_))),
+            "{:?}",
+            result
+        );
+        assert!(!written);
+    }
+
+    #[test]
+    fn pagetoc_keeps_customized_fonts() {
+        let root = test_dir("pagetoc-fonts");
+        let css = root.join("theme/css");
+        fs::create_dir_all(&css).unwrap();
+        let general = String::from_utf8_lossy(mdbook::theme::GENERAL_CSS).replace(
+            r#"font-family: "Open Sans", sans-serif"#,
+            "font-family: Inter",
+        );
+        let variables = String::from_utf8_lossy(mdbook::theme::VARIABLES_CSS).replacen(
+            "--mono-font: ",
+            "--mono-font: Iosevka, ",
+            1,
+        );
+        assert!(general.contains("Inter") && variables.contains("Iosevka"));
+        fs::write(css.join("general.css"), general).unwrap();
+        fs::write(css.join("variables.css"), variables).unwrap();
+        run_toml("pagetoc = true", &root).unwrap();
+
+        let general = read(&root, "css/general.css");
+        let variables = read(&root, "css/variables.css");
+        fs::remove_dir_all(&root).unwrap();
+        assert!(general.contains("font-family: Inter;"), "{}", general);
+        assert!(
+            variables.contains("--mono-font: Iosevka, "),
+            "{}",
+            variables
+        );
+    }
+
+    #[test]
+    fn english_labels_only_for_keys_not_set() {
+        let strings = |t: &str| toml::from_str::<toml::Value>(t).unwrap();
+        let english =
+            |value: Option<&toml::Value>, language| labels_from(value, language).unwrap().1;
+        assert!(english(None, "zh-CN").is_empty());
+        assert_eq!(english(None, "xx"), crate::theme::strings::KEYS);
+        let some = strings("on-this-page = \"Auf dieser Seite\"");
+        assert_eq!(
+            english(Some(&some), "xx"),
+            ["collapse-all", "toggle-subsections"]
+        );
+        let all = strings("on-this-page = \"a\"\ncollapse-all = \"b\"\ntoggle-subsections = \"c\"");
+        assert!(english(Some(&all), "xx").is_empty());
+        let (labels, _) = labels_from(Some(&all), "xx").unwrap();
+        assert_eq!(labels.get("collapse-all"), "b");
+    }
 }
@@ -0,0 +1,350 @@
+//! `contrast = "AA"` : check the text colours of every theme against their backgrounds after
+//! the edits, with the WCAG contrast ratio.
+//!
+//! Pairs are resolved from `css/variables.css` (following `var()`), and inline code is checked
+//! against `--code-bg` , or the `.hljs` background of the highlight stylesheet mdBook loads for
+//! the theme. Only pairs with a colour set or derived by the config are checked: the others are
+//! mdBook's choices.
+
+use super::{
+    color::Rgba,
+    css::{Rule, StyleRule, Stylesheet},
+    output::Output,
+    Content, CssFile,
+};
+use crate::{ace::Ace, Result};
+use std::path::Path;
+use toml::value::Value as MdValue;
+
+/// text colours and their backgrounds (variables without `--`)
+const PAIRS: &[(&str, &str)] = &[
+    ("fg", "bg"),
+    ("links", "bg"),
+    ("inline-code-color", "code-bg"),
+    ("sidebar-fg", "sidebar-bg"),
+    ("sidebar-active", "sidebar-bg"),
+    ("searchbar-fg", "searchbar-bg"),
+];
+
+/// `css/variables.css` and the highlight stylesheets
+const FILES: &[&str] = &[
+    "css/variables.css",
+    "highlight.css",
+    "tomorrow-night.css",
+    "ayu-highlight.css",
+];
+
+/// how deep `var()` is followed
+const MAX_DEPTH: usize = 8;
+
+/// The minimal ratio from `contrast` : `"AA"` (4.5, the default), `"AAA"` (7), a number from 1
+/// to 21, or `false` to skip the check.
+pub fn ratio(value: Option<&MdValue>) -> std::result::Result<Option<f64>, String> {
+    let ratio = match value {
+        None => 4.5,
+        Some(MdValue::Boolean(false)) => return Ok(None),
+        Some(MdValue::String(s)) => match s.to_ascii_uppercase().as_str() {
+            "AA" => 4.5,
+            "AAA" => 7.0,
+            "OFF" => return Ok(None),
+            _ => s
+                .parse()
+                .map_err(|_| format!("expected \"AA\" or \"AAA\", got `{s}`"))?,
+        },
+        Some(MdValue::Integer(n)) => *n as f64,
+        Some(MdValue::Float(n)) => *n,
+        Some(v) => {
+            return Err(format!(
+                "expected \"AA\", \"AAA\", a number or false, got `{v}`"
+            ))
+        }
+    };
+    if (1.0..=21.0).contains(&ratio) {
+        Ok(Some(ratio))
+    } else {
+        Err(format!("a contrast ratio is from 1 to 21, got `{ratio}`"))
+    }
+}
+
+/// A text colour and its background in a theme.
+#[derive(Clone, Debug, PartialEq)]
+struct Pair {
+    theme: String,
+    fg: &'static str,
+    bg_name: &'static str,
+    bg: String,
+    colors: (Rgba, Rgba),
+}
+
+impl Pair {
+    fn describe(&self, ratio: f64) -> String {
+        format!(
+            "`.{}` : `--{}` ({}) on {} ({}) has a contrast ratio of {:.2}:1, below {ratio}:1",
+            self.theme,
+            self.fg,
+            self.colors.0,
+            self.bg,
+            self.colors.1,
+            self.colors.0.contrast(self.colors.1)
+        )
+    }
+}
+
+/// Pairs in the theme files (as they would be written) below `ratio` , of which a colour is in
+/// `configured` : `(theme, variable without --)` set or derived by the config.
+pub fn themes(
+    dir: &Path,
+    output: &Output,
+    ratio: f64,
+    configured: &[(&str, &str)],
+) -> Result<Vec<String>> {
+    let mut edited = Vec::new();
+    for f in FILES {
+        edited.push((*f, Content::from(CssFile::Custom(f), dir, output)?));
+    }
+    let edited: Vec<_> = edited.iter().map(|(f, c)| (*f, c.get())).collect();
+    let configured = |p: &Pair| {
+        configured
+            .iter()
+            .any(|&(t, v)| t == p.theme && (v == p.fg || v == p.bg_name))
+    };
+    Ok(pairs(&edited)
+        .into_iter()
+        .filter(configured)
+        .filter(|p| p.colors.0.contrast(p.colors.1) < ratio)
+        .map(|p| p.describe(ratio))
+        .collect())
+}
+
+/// The text of ace themes in `output.theme-ace` against their backgrounds, if below `ratio` .
+/// An ace theme that can't be read is warned and left to the backend `mdbook-theme-ace` .
+pub fn ace(ace: &Ace, ratio: f64) -> Vec<String> {
+    let mut low = Vec::new();
+    for (dark, name) in [(false, &ace.theme_white), (true, &ace.theme_dark)] {
+        if name.is_empty() {
+            continue;
+        }
+        let (class, text) = match ace.css_class_text(dark) {
+            Ok(css) => css,
+            Err(e) => {
+                eprintln!("Warning: the contrast of ace theme `{name}` is not checked: {e}");
+                continue;
+            }
+        };
+        let sheet = Stylesheet::parse(&text);
+        let selector = format!(".{class}");
+        let value = |property: &str| {
+            let rule = sheet.rule(&selector, Some(property))?;
+            let decl = rule.declaration(property)?;
+            Rgba::parse(&text[decl.value.clone()]).ok()
+        };
+        let bg = value("background-color").or_else(|| value("background"));
+        if let (Some(fg), Some(bg)) = (value("color"), bg) {
+            let bg = over(bg, Rgba::WHITE);
+            let fg = over(fg, bg);
+            if fg.contrast(bg) < ratio {
+                let item = if dark { "theme-dark" } else { "theme-white" };
+                low.push(format!(
+                    "ace theme `{name}` ({item}): text ({fg}) on `{selector}` ({bg}) has a \
+                     contrast ratio of {:.2}:1, below {ratio}:1",
+                    fg.contrast(bg)
+                ));
+            }
+        }
+    }
+    low
+}
+
+/// The pairs of every theme, i.e. a class with `--bg` , in `css/variables.css` .
+fn pairs(files: &[(&str, &str)]) -> Vec<Pair> {
+    let text = |file: &str| {
+        files
+            .iter()
+            .find(|(f, _)| *f == file)
+            .map_or("", |(_, t)| t)
+    };
+    let src = text(FILES[0]);
+    let variables = Stylesheet::parse(src);
+    let root = variables.rule(":root", None);
+    let themes = variables.rules.iter().filter_map(|r| match r {
+        Rule::Style(s) if s.declaration("--bg").is_some() => Some(s),
+        _ => None,
+    });
+    let mut pairs = Vec::new();
+    for rule in themes {
+        for theme in rule.selectors.iter().filter_map(|s| class(s)) {
+            let get = |name: &str| resolve(src, &[Some(rule), root], name, 0);
+            let page = match get("bg") {
+                Some(bg) => over(bg, Rgba::WHITE),
+                None => continue,
+            };
+            for (fg, bg_name) in PAIRS {
+                let (bg, label) = match (get(bg_name), *bg_name) {
+                    (Some(bg), _) => (bg, format!("`--{bg_name}`")),
+                    (None, "code-bg") => match hljs_bg(text(highlight(theme))) {
+                        Some(bg) => (bg, format!("`.hljs` in {}", highlight(theme))),
+                        None => continue,
+                    },
+                    (None, _) => continue,
+                };
+                let bg = over(bg, page);
+                if let Some(color) = get(fg) {
+                    pairs.push(Pair {
+                        theme: theme.to_string(),
+                        fg,
+                        bg_name,
+                        bg: label,
+                        colors: (over(color, bg), bg),
+                    });
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// `name` in `.name`
+fn class(selector: &str) -> Option<&str> {
+    let name = selector.strip_prefix('.')?;
+    let plain = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    (!name.is_empty() && name.chars().all(plain)).then_some(name)
+}
+
+/// The colour of `--name` , looked up in `rules` in order and following `var()` .
+fn resolve(src: &str, rules: &[Option<&StyleRule>], name: &str, depth: usize) -> Option<Rgba> {
+    let property = format!("--{name}");
+    let decl = rules
+        .iter()
+        .flatten()
+        .find_map(|r| r.declaration(&property))?;
+    value(src, rules, &src[decl.value.clone()], depth)
+}
+
+fn value(src: &str, rules: &[Option<&StyleRule>], value: &str, depth: usize) -> Option<Rgba> {
+    let value = value.trim();
+    match value.strip_prefix("var(").and_then(|v| v.strip_suffix(')')) {
+        Some(_) if depth >= MAX_DEPTH => None,
+        Some(args) => {
+            let (name, fallback) = args.split_once(',').unwrap_or((args, ""));
+            let name = name.trim().strip_prefix("--")?;
+            resolve(src, rules, name, depth + 1)
+                .or_else(|| self::value(src, rules, fallback, depth + 1))
+        }
+        None => Rgba::parse(value).ok(),
+    }
+}
+
+/// The highlight stylesheet mdBook enables for a theme (see `book.js`).
+fn highlight(theme: &str) -> &'static str {
+    match theme {
+        "coal" | "navy" => "tomorrow-night.css",
+        "ayu" => "ayu-highlight.css",
+        _ => "highlight.css",
+    }
+}
+
+/// The background of `.hljs` , which inline code has as well.
+fn hljs_bg(src: &str) -> Option<Rgba> {
+    let sheet = Stylesheet::parse(src);
+    let find = |property: &str| {
+        let decl = sheet.rule(".hljs", Some(property))?.declaration(property)?;
+        Rgba::parse(&src[decl.value.clone()]).ok()
+    };
+    find("background").or_else(|| find("background-color"))
+}
+
+/// `color` painted over an opaque `bg`
+fn over(color: Rgba, bg: Rgba) -> Rgba {
+    Rgba {
+        a: 1.0,
+        ..bg.mix(color, color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pairs, ratio, resolve, Pair, FILES};
+    use crate::theme::{color::Rgba, css::Stylesheet};
+    use toml::value::Value;
+
+    fn ratio_of(value: Value) -> Result<Option<f64>, String> {
+        ratio(Some(&value))
+    }
+
+    #[test]
+    fn ratios() {
+        assert_eq!(ratio(None), Ok(Some(4.5)));
+        assert_eq!(ratio_of(Value::String("aa".into())), Ok(Some(4.5)));
+        assert_eq!(ratio_of(Value::String("AAA".into())), Ok(Some(7.0)));
+        assert_eq!(ratio_of(Value::String("3".into())), Ok(Some(3.0)));
+        assert_eq!(ratio_of(Value::Integer(3)), Ok(Some(3.0)));
+        assert_eq!(ratio_of(Value::Float(2.5)), Ok(Some(2.5)));
+        assert_eq!(ratio_of(Value::Boolean(false)), Ok(None));
+        assert_eq!(ratio_of(Value::String("off".into())), Ok(None));
+        assert!(ratio_of(Value::Boolean(true)).is_err());
+        assert!(ratio_of(Value::Integer(22)).is_err());
+        assert!(ratio_of(Value::Float(0.5)).is_err());
+        assert!(ratio_of(Value::String("A".into())).is_err());
+    }
+
+    const VARIABLES: &str = "
+        :root { --accent: #0000ff; --sidebar-bg: #000000; }
+        .light, html:not(.js) {
+            --bg: #ffffff;
+            --fg: var(--text, #333333);
+            --links: var(--accent);
+            --sidebar-fg: #ffffff80;
+            --loop: var(--loop);
+        }
+        .coal { --bg: #000000; --fg: #ffffff; --inline-code-color: #111111; }
+    ";
+
+    fn find<'p>(pairs: &'p [Pair], theme: &str, fg: &str) -> Option<&'p Pair> {
+        pairs.iter().find(|p| p.theme == theme && p.fg == fg)
+    }
+
+    #[test]
+    fn var_is_followed() {
+        let sheet = Stylesheet::parse(VARIABLES);
+        let (root, light) = (sheet.rule(":root", None), sheet.rule(".light", None));
+        let get = |name: &str| resolve(VARIABLES, &[light, root], name, 0);
+        assert_eq!(get("links"), Some(Rgba::parse("#0000ff").unwrap()));
+        assert_eq!(
+            get("fg"),
+            Some(Rgba::parse("#333333").unwrap()),
+            "the fallback"
+        );
+        assert_eq!(get("sidebar-bg"), Some(Rgba::BLACK), "from :root");
+        assert_eq!(get("loop"), None);
+        assert_eq!(get("nothing"), None);
+    }
+
+    #[test]
+    fn pairs_of_every_theme() {
+        let highlight = ".hljs { background: #f6f7f6; color: #000; }";
+        let night = ".hljs { background: #1d1f21; }";
+        let files = [
+            (FILES[0], VARIABLES),
+            ("highlight.css", highlight),
+            ("tomorrow-night.css", night),
+        ];
+        let pairs = pairs(&files);
+
+        let links = find(&pairs, "light", "links").unwrap();
+        assert_eq!(links.bg, "`--bg`");
+        assert!((links.colors.0.contrast(links.colors.1) - 8.59).abs() < 0.01);
+        // a translucent colour is painted over its background
+        let sidebar = find(&pairs, "light", "sidebar-fg").unwrap();
+        assert_eq!(sidebar.colors.0.to_string(), "#808080");
+        // `html:not(.js)` is not a theme, and `--code-bg` falls back to `.hljs`
+        assert!(pairs
+            .iter()
+            .all(|p| p.theme == "light" || p.theme == "coal"));
+        let code = find(&pairs, "coal", "inline-code-color").unwrap();
+        assert_eq!(code.bg, "`.hljs` in tomorrow-night.css");
+        assert_eq!(code.colors.1.to_string(), "#1d1f21");
+        // no colour: no pair
+        assert!(find(&pairs, "light", "inline-code-color").is_none());
+        assert!(find(&pairs, "coal", "links").is_none());
+    }
+}
@@ -0,0 +1,193 @@
+//! Convert non-string toml values into css values, such as `sidebar-width = 300` into `300px`
+//! and `content-padding = [0, 10]` into `0 10px` .
+//!
+//! What a bare number means is guessed from the item (or property) name. CSS has no booleans:
+//! `false` turns a property off with `none` , and `true` leaves it to the browser with `auto` ;
+//! an item that takes neither still fails its value check.
+
+use toml::value::Value as MdValue;
+
+/// generic font families that must not be quoted
+const GENERIC_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "ui-serif",
+    "ui-sans-serif",
+    "ui-monospace",
+    "ui-rounded",
+    "emoji",
+    "math",
+    "fangsong",
+    "inherit",
+    "initial",
+    "unset",
+];
+
+/// words in an item name that mean a length
+const LENGTH_WORDS: &[&str] = &[
+    "width", "height", "padding", "margin", "left", "right", "top", "bottom", "gap", "indent",
+    "radius", "spacing", "offset",
+];
+
+enum Kind {
+    Length,
+    FontSize,
+    LineHeight,
+    FontFamily,
+    Other,
+}
+
+fn kind(item: &str) -> Kind {
+    if item.ends_with("line-height") {
+        Kind::LineHeight
+    } else if item.ends_with("font-family") {
+        Kind::FontFamily
+    } else if item.ends_with("font-size") || item.ends_with("fontsize") {
+        Kind::FontSize
+    } else if item.split('-').any(|w| LENGTH_WORDS.contains(&w)) {
+        Kind::Length
+    } else {
+        Kind::Other
+    }
+}
+
+/// Get the css value of `item` , or the reason why the value can't be used.
+pub fn css_value(item: &str, value: &MdValue) -> Result<String, String> {
+    match value {
+        MdValue::String(s) => Ok(s.clone()),
+        MdValue::Integer(i) => number(item, *i as f64),
+        MdValue::Float(f) => number(item, *f),
+        MdValue::Array(a) => array(item, a),
+        MdValue::Boolean(b) => boolean(item, *b),
+        v => Err(format!(
+            "expected a string, a number, a boolean or an array, but got a {}",
+            v.type_str()
+        )),
+    }
+}
+
+/// * lengths: `px` (`0` stays unitless)
+/// * font sizes: `em` if less than 4 (e.g. `0.9` ), otherwise `px` (e.g. `14.5` )
+/// * line heights and others: unitless
+fn number(item: &str, n: f64) -> Result<String, String> {
+    let s = if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    };
+    match kind(item) {
+        Kind::Length if n == 0.0 => Ok(s),
+        Kind::Length => Ok(format!("{s}px")),
+        Kind::FontSize if n.abs() < 4.0 => Ok(format!("{s}em")),
+        Kind::FontSize => Ok(format!("{s}px")),
+        Kind::FontFamily => Err(format!("expected font names, but got a number `{s}`")),
+        Kind::LineHeight | Kind::Other => Ok(s),
+    }
+}
+
+/// `false` is `none` and `true` is `auto` , except for font families, which take neither.
+fn boolean(item: &str, b: bool) -> Result<String, String> {
+    match kind(item) {
+        Kind::FontFamily => Err(format!("expected font names, but got `{b}`")),
+        _ if b => Ok(String::from("auto")),
+        _ => Ok(String::from("none")),
+    }
+}
+
+/// * font families: names are quoted and joined with `, `
+/// * others: converted one by one and joined with ` ` , e.g. shorthands like `padding`
+fn array(item: &str, a: &[MdValue]) -> Result<String, String> {
+    let v = match kind(item) {
+        Kind::FontFamily => a
+            .iter()
+            .map(|v| v.as_str().map(font_family).ok_or("expected font names"))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => a
+            .iter()
+            .map(|v| match v {
+                MdValue::Array(_) => Err("nested arrays are not allowed".to_string()),
+                v => css_value(item, v),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let sep = if let Kind::FontFamily = kind(item) {
+        ", "
+    } else {
+        " "
+    };
+    Ok(v.join(sep))
+}
+
+/// Quote a font name unless it's a generic family, a `var()` or already quoted.
+fn font_family(name: &str) -> String {
+    let name = name.trim();
+    if GENERIC_FAMILIES.contains(&name)
+        || name.starts_with("var(")
+        || name.starts_with('"')
+        || name.starts_with('\'')
+    {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::css_value;
+    use toml::value::Value;
+
+    fn css(item: &str, toml: &str) -> Result<String, String> {
+        let value: toml::map::Map<String, Value> = toml::from_str(&format!("v = {toml}")).unwrap();
+        css_value(item, &value["v"])
+    }
+
+    #[test]
+    fn strings_are_kept() {
+        assert_eq!(css("sidebar-width", r#""30%""#).unwrap(), "30%");
+        assert_eq!(
+            css("color", r#""not checked here""#).unwrap(),
+            "not checked here"
+        );
+    }
+
+    #[test]
+    fn numbers_by_item() {
+        assert_eq!(css("sidebar-width", "300").unwrap(), "300px");
+        assert_eq!(css("page-padding", "0").unwrap(), "0");
+        assert_eq!(css("root-font-size", "0.7").unwrap(), "0.7em");
+        assert_eq!(css("pagetoc-fontsize", "14.5").unwrap(), "14.5px");
+        assert_eq!(css("chapter-line-height", "2").unwrap(), "2");
+        assert_eq!(css("opacity", "0.5").unwrap(), "0.5");
+        assert!(css("body-font-family", "1").is_err());
+    }
+
+    #[test]
+    fn arrays_are_joined() {
+        assert_eq!(css("content-padding", "[0, 10]").unwrap(), "0 10px");
+        assert_eq!(css("margin", r#"["auto", 2.5]"#).unwrap(), "auto 2.5px");
+        assert_eq!(
+            css(
+                "font-family",
+                r#"["Inter", "sans-serif", "var(--x)", "'A B'"]"#
+            )
+            .unwrap(),
+            r#""Inter", sans-serif, var(--x), 'A B'"#
+        );
+        assert!(css("margin", "[[0]]").is_err());
+        assert!(css("font-family", "[1]").is_err());
+    }
+
+    #[test]
+    fn booleans_are_keywords() {
+        assert_eq!(css("nav-chapters-max-width", "true").unwrap(), "auto");
+        assert_eq!(css("display", "false").unwrap(), "none");
+        assert!(css("code-font-family", "true").is_err());
+        let date = Value::Datetime("1979-05-27".parse().unwrap());
+        assert!(css_value("sidebar-width", &date).is_err());
+    }
+}
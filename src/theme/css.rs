@@ -0,0 +1,485 @@
+//! A tiny css parser: it knows rules, selectors, at-rules (like `@media`) and declarations,
+//! which is just enough to locate what to modify in a stylesheet.
+//!
+//! Nothing is rebuilt from the parsed model: every node only records byte ranges into the
+//! source text, so an edit replaces exactly the bytes of a value and leaves everything else
+//! (comments, blank lines, indentation, rules we don't care about) untouched.
+//!
+//! Rules nested in a style rule (nested css like `.a { .b { ... } }`) are skipped rather than
+//! modelled: the declarations around them still belong to the outer rule.
+
+use std::ops::Range;
+
+/// at-rules whose block contains rules rather than declarations
+const NESTED_AT_RULES: &[&str] = &[
+    "media",
+    "supports",
+    "document",
+    "layer",
+    "container",
+    "scope",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Style(StyleRule),
+    At(AtRule),
+}
+
+/// `selector, selector { declarations }`
+#[derive(Debug, Clone)]
+pub struct StyleRule {
+    /// comment-free and whitespace-collapsed selectors
+    pub selectors: Vec<String>,
+    /// from the first char of the selectors to the char right after `}`
+    pub span: Range<usize>,
+    /// between `{` and `}`
+    pub block: Range<usize>,
+    pub declarations: Vec<Declaration>,
+}
+
+/// `@name prelude { ... }` or `@name prelude;`
+#[derive(Debug, Clone)]
+pub struct AtRule {
+    /// the name without `@`, such as `media`
+    pub name: String,
+    /// comment-free and whitespace-collapsed, such as `@media only screen and (max-width:1439px)`
+    pub prelude: String,
+    pub span: Range<usize>,
+    pub block: Option<Range<usize>>,
+    /// nested rules in `@media`, `@supports`, ...
+    pub rules: Vec<Rule>,
+    /// declarations in `@font-face`, `@page`, ...
+    pub declarations: Vec<Declaration>,
+}
+
+/// `property: value !important;`
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub property: String,
+    /// from the first char of the property to the char right after `;` (if any)
+    pub span: Range<usize>,
+    /// the value without surrounding whitespace, comments and `!important`
+    pub value: Range<usize>,
+    /// whether the declaration ends with `;`
+    pub terminated: bool,
+}
+
+impl Stylesheet {
+    pub fn parse(text: &str) -> Self {
+        let mut parser = Parser {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+            skipped: None,
+        };
+        Stylesheet {
+            rules: parser.rules(false),
+        }
+    }
+
+    /// The first top-level rule matching `selector` that declares `property`,
+    /// or the first top-level rule matching `selector` if none declares it.
+    pub fn rule(&self, selector: &str, property: Option<&str>) -> Option<&StyleRule> {
+        find_rule(&self.rules, selector, property)
+    }
+
+    /// The first top-level at-rule whose prelude is `prelude`.
+    pub fn at_rule(&self, prelude: &str) -> Option<&AtRule> {
+        let prelude = normalize(prelude);
+        self.rules.iter().find_map(|r| match r {
+            Rule::At(a) if a.prelude == prelude => Some(a),
+            _ => None,
+        })
+    }
+}
+
+impl StyleRule {
+    pub fn declaration(&self, property: &str) -> Option<&Declaration> {
+        self.declarations.iter().find(|d| d.property == property)
+    }
+}
+
+impl AtRule {
+    pub fn rule(&self, selector: &str, property: Option<&str>) -> Option<&StyleRule> {
+        find_rule(&self.rules, selector, property)
+    }
+}
+
+fn find_rule<'r>(
+    rules: &'r [Rule],
+    selector: &str,
+    property: Option<&str>,
+) -> Option<&'r StyleRule> {
+    let selector = normalize(selector);
+    let mut candidates = rules.iter().filter_map(|r| match r {
+        Rule::Style(s) if s.selectors.contains(&selector) => Some(s),
+        _ => None,
+    });
+    let first = candidates.clone().next();
+    property
+        .and_then(|p| candidates.find(|s| s.declaration(p).is_some()))
+        .or(first)
+}
+
+/// Strip comments and collapse whitespace, so that selectors and preludes written
+/// in different layouts compare equal.
+pub fn normalize(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(p) = rest.find("/*") {
+        out.push_str(&rest[..p]);
+        out.push(' ');
+        rest = rest[p + 2..]
+            .find("*/")
+            .map_or("", |e| &rest[p + 2 + e + 2..]);
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split selectors on commas that are not inside `()` or `[]`.
+fn split_selectors(prelude: &str) -> Vec<String> {
+    let prelude = normalize(prelude);
+    let (mut depth, mut start, mut v) = (0i32, 0, Vec::new());
+    for (i, c) in prelude.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                v.push(prelude[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    v.push(prelude[start..].trim().to_string());
+    v.retain(|s| !s.is_empty());
+    v
+}
+
+/// Css syntax chars are all ASCII, so it's safe to scan the bytes of a utf-8 str.
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    /// right after the `}` of the last block skipped by `scan_to`
+    skipped: Option<usize>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn starts_comment(&self) -> bool {
+        self.bytes[self.pos..].starts_with(b"/*")
+    }
+
+    fn skip_comment(&mut self) {
+        self.pos = self.text[self.pos + 2..]
+            .find("*/")
+            .map_or(self.bytes.len(), |p| self.pos + 2 + p + 2);
+    }
+
+    fn skip_string(&mut self, quote: u8) {
+        self.pos += 1;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'\\' => self.pos += 1,
+                c if c == quote => return,
+                _ => (),
+            }
+        }
+        self.pos = self.pos.min(self.bytes.len());
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else if self.starts_comment() {
+                self.skip_comment();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Move to the first of `stops` that is outside of strings, comments and brackets,
+    /// and return the end of the last significant (non-whitespace and non-comment) char.
+    fn scan_to(&mut self, stops: &[u8]) -> usize {
+        let (mut depth, mut last) = (0usize, self.pos);
+        while let Some(c) = self.peek() {
+            match c {
+                _ if depth == 0 && stops.contains(&c) => break,
+                b'/' if self.starts_comment() => {
+                    self.skip_comment();
+                    continue;
+                }
+                b'"' | b'\'' => {
+                    self.skip_string(c);
+                    last = self.pos;
+                    continue;
+                }
+                b'(' | b'[' => depth += 1,
+                b')' | b']' => depth = depth.saturating_sub(1),
+                b'{' => {
+                    // a block where it's not expected, e.g. nested css: skip it as a whole
+                    self.pos += 1;
+                    self.scan_to(b"}");
+                    self.skipped = Some((self.pos + 1).min(self.bytes.len()));
+                }
+                _ => (),
+            }
+            if !c.is_ascii_whitespace() {
+                last = self.pos + 1;
+            }
+            self.pos += 1;
+        }
+        self.pos = self.pos.min(self.bytes.len());
+        last.min(self.bytes.len())
+    }
+
+    /// Parse rules until the end of text or an unmatched `}` if `nested` .
+    fn rules(&mut self, nested: bool) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => break,
+                Some(b'}') if nested => break,
+                Some(b'}') | Some(b';') => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => (),
+            }
+            let start = self.pos;
+            let prelude_end = self.scan_to(b"{;}");
+            let prelude = &self.text[start..prelude_end];
+            if prelude.starts_with('@') {
+                rules.push(Rule::At(self.at_rule(start, prelude)));
+            } else if self.peek() == Some(b'{') {
+                self.pos += 1;
+                let block_start = self.pos;
+                let declarations = self.declarations();
+                let block = block_start..self.pos;
+                self.pos = (self.pos + 1).min(self.bytes.len());
+                rules.push(Rule::Style(StyleRule {
+                    selectors: split_selectors(prelude),
+                    span: start..self.pos,
+                    block,
+                    declarations,
+                }));
+            } else {
+                // a stray statement: skip it
+                self.pos = (self.pos + 1).min(self.bytes.len());
+            }
+        }
+        rules
+    }
+
+    fn at_rule(&mut self, start: usize, prelude: &str) -> AtRule {
+        let name: String = prelude[1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '-')
+            .collect();
+        let mut at = AtRule {
+            prelude: normalize(prelude),
+            name,
+            span: start..start,
+            block: None,
+            rules: Vec::new(),
+            declarations: Vec::new(),
+        };
+        if self.peek() == Some(b'{') {
+            self.pos += 1;
+            let block_start = self.pos;
+            if NESTED_AT_RULES.contains(&at.name.to_ascii_lowercase().as_str()) {
+                at.rules = self.rules(true);
+            } else {
+                at.declarations = self.declarations();
+            }
+            at.block = Some(block_start..self.pos);
+        }
+        // consume `;` or `}`
+        self.pos = (self.pos + 1).min(self.bytes.len());
+        at.span = start..self.pos;
+        at
+    }
+
+    /// Parse declarations until the end of text or `}` .
+    fn declarations(&mut self) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None | Some(b'}') => break,
+                Some(b';') => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => (),
+            }
+            let start = self.pos;
+            self.skipped = None;
+            let property_end = self.scan_to(b":;}");
+            if let Some(end) = self.skipped {
+                // a nested rule like `.b { ... }`
+                self.pos = end;
+                continue;
+            }
+            if self.peek() != Some(b':') {
+                // not a declaration
+                if self.peek() == Some(b';') {
+                    self.pos += 1;
+                }
+                continue;
+            }
+            let property = self.text[start..property_end].trim().to_string();
+            self.pos += 1;
+            self.skip_trivia();
+            let value_start = self.pos;
+            let mut value_end = self.scan_to(b";}").max(value_start);
+            if let Some(end) = self.skipped {
+                // a nested rule with a pseudo-class like `&:hover { ... }`
+                self.pos = end;
+                continue;
+            }
+            if let Some(p) = important(&self.text[value_start..value_end]) {
+                value_end = value_start + p;
+            }
+            let terminated = self.peek() == Some(b';');
+            if terminated {
+                self.pos += 1;
+            }
+            let end = if terminated { self.pos } else { value_end };
+            declarations.push(Declaration {
+                property,
+                span: start..end,
+                value: value_start..value_end,
+                terminated,
+            });
+        }
+        declarations
+    }
+}
+
+/// The end of a value without a trailing `!important` .
+fn important(value: &str) -> Option<usize> {
+    let lower = value.to_ascii_lowercase();
+    let rest = lower.strip_suffix("important")?.trim_end();
+    rest.strip_suffix('!').map(|v| v.trim_end().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the text of the value of `property` in the first rule of `selector`
+    fn value<'t>(text: &'t str, sheet: &Stylesheet, selector: &str, property: &str) -> &'t str {
+        let rule = sheet.rule(selector, Some(property)).expect("a rule");
+        &text[rule
+            .declaration(property)
+            .expect("a declaration")
+            .value
+            .clone()]
+    }
+
+    #[test]
+    fn comments() {
+        let text = "/* a */ .a /* b */ ,\n.b { /* c */ color: /* d */ red /* e */; }";
+        let sheet = Stylesheet::parse(text);
+        match &sheet.rules[..] {
+            [Rule::Style(rule)] => assert_eq!(rule.selectors, [".a", ".b"]),
+            rules => panic!("{:?}", rules),
+        }
+        assert_eq!(value(text, &sheet, ".b", "color"), "red");
+    }
+
+    #[test]
+    fn minified() {
+        let text = ".a{color:red}.b{margin:0;padding:1px}";
+        let sheet = Stylesheet::parse(text);
+        assert_eq!(sheet.rules.len(), 2);
+        assert_eq!(value(text, &sheet, ".a", "color"), "red");
+        assert_eq!(value(text, &sheet, ".b", "margin"), "0");
+        assert_eq!(value(text, &sheet, ".b", "padding"), "1px");
+        let a = sheet.rule(".a", None).unwrap();
+        assert!(!a.declaration("color").unwrap().terminated);
+        assert_eq!(&text[a.span.clone()], ".a{color:red}");
+    }
+
+    #[test]
+    fn important() {
+        let text = ".a { color: red !important; margin: 0 ! IMPORTANT }";
+        let sheet = Stylesheet::parse(text);
+        assert_eq!(value(text, &sheet, ".a", "color"), "red");
+        assert_eq!(value(text, &sheet, ".a", "margin"), "0");
+    }
+
+    #[test]
+    fn strings() {
+        let text = r#".a { content: "a;b}"; font-family: 'x}{y', serif; color: red }"#;
+        let sheet = Stylesheet::parse(text);
+        assert_eq!(value(text, &sheet, ".a", "content"), r#""a;b}""#);
+        assert_eq!(value(text, &sheet, ".a", "font-family"), "'x}{y', serif");
+        assert_eq!(value(text, &sheet, ".a", "color"), "red");
+    }
+
+    #[test]
+    fn media() {
+        let text = "@media (max-width: 10px) {\n  .a { color: red }\n}\n.a { color: blue }";
+        let sheet = Stylesheet::parse(text);
+        let at = sheet
+            .at_rule("@media   (max-width: 10px)")
+            .expect("an at-rule");
+        assert_eq!(at.name, "media");
+        let rule = at.rule(".a", Some("color")).unwrap();
+        assert_eq!(
+            &text[rule.declaration("color").unwrap().value.clone()],
+            "red"
+        );
+        assert_eq!(value(text, &sheet, ".a", "color"), "blue");
+    }
+
+    #[test]
+    fn unterminated() {
+        let text = ".a { color: red";
+        let sheet = Stylesheet::parse(text);
+        let decl = sheet
+            .rule(".a", None)
+            .unwrap()
+            .declaration("color")
+            .unwrap();
+        assert_eq!(&text[decl.value.clone()], "red");
+        assert!(!decl.terminated);
+
+        let text = "@media print { .a { color: red }";
+        let sheet = Stylesheet::parse(text);
+        let at = sheet.at_rule("@media print").unwrap();
+        assert!(at.rule(".a", Some("color")).is_some());
+
+        let text = ".a { content: \"open; /* open";
+        let sheet = Stylesheet::parse(text);
+        assert!(sheet.rule(".a", None).is_some());
+    }
+
+    #[test]
+    fn nested() {
+        let text = ".h { a: b; .x { c: d } e: f; &:hover { g: h } i: j }";
+        let sheet = Stylesheet::parse(text);
+        let rule = sheet.rule(".h", None).unwrap();
+        let properties: Vec<_> = rule.declarations.iter().map(|d| &*d.property).collect();
+        assert_eq!(properties, ["a", "e", "i"]);
+        assert_eq!(value(text, &sheet, ".h", "e"), "f");
+        assert_eq!(value(text, &sheet, ".h", "i"), "j");
+    }
+}
@@ -1,7 +1,7 @@
-use super::{CssFile, Item, Value};
+use super::{CssFile, Item, Value, ValueType};
 
 #[rustfmt::skip]
-pub static CSSFILES: &[(CssFile, &str)] = 
+pub static CSSFILES: &[(CssFile<'static>, &str)] = 
     &[default!(Variables,  "css/variables.css"),
       default!(Index,      "index.hbs"),
       default!(PagetocJs,  "pagetoc.js"),
@@ -9,39 +9,99 @@ pub static CSSFILES: &[(CssFile, &str)] =
       default!(General,    "css/general.css"),
       default!(Chrome,     "css/chrome.css")];
 
+/// `font-family` of `html` in mdBook's `css/general.css`
+const BODY_FONT: &str = r#""Open Sans", sans-serif"#;
+
+/// `--mono-font` in mdBook's `css/variables.css`
+const MONO_FONT: &str = r#""Source Code Pro", Consolas, "Ubuntu Mono", Menlo, "DejaVu Sans Mono", monospace, monospace"#;
+
 #[rustfmt::skip]
-pub static DEFAULT: &[(CssFile, Item, Value)] =
-    &[/*                   pagetoc related                      */
-      default!(Variables, "sidebar-width",             "140px"),
-      default!(Variables, "page-padding",              "15px"),
-      default!(Variables, "content-max-width",         "82%"),
-      default!(Variables, "menu-bar-height",           "40px"),
-      default!(Variables, "pagetoc-width",             "13%"),
-      default!(Variables, "pagetoc-fontsize",          "14.5px"),
-      default!(Variables, "mobile-content-max-width",  "98%"),
-      default!(General,   "content-padding",           "0 10px"),
-      default!(General,   "content-main-margin-left",  "2%"),
-      default!(General,   "content-main-margin-right", "2%"),
-      default!(Chrome,    "nav-chapters-max-width",    "auto"),
-      default!(Chrome,    "nav-chapters-min-width",    "auto"),
-      default!(Chrome,    "chapter-line-height",       "2em"),
-      default!(Chrome,    "section-line-height",       "1.5em"),
-      /*                   font-size related                    */
-      default!(General,   "root-font-size",            "70%"),
-      default!(General,   "body-font-size",            "1.5rem"),
-      default!(General,   "code-font-size",            "0.9em"),
-      default!(Chrome,    "sidebar-font-size",         "1em"),
-      /*                   color related                        */
-      default!(Variables, "light-links",               "#1f1fff"),
-      default!(Variables, "light-inline-code-color",   "#F42C4C"),
-      default!(Variables, "rust-links",                "#2b79a2"),
-      default!(Variables, "rust-inline-code-color",    "#6e6b5e"),
-      default!(Variables, "navy-links",                "#2b79a2"),
-      default!(Variables, "navy-inline-code-color",    "#c5c8c6"),
-      default!(Variables, "coal-links",                "#2b79a2"),
-      default!(Variables, "coal-inline-code-color",    "#c5c8c6"),
-      default!(Variables, "ayu-links",                 "#0096cf"),
-      default!(Variables, "ayu-inline-code-color",     "#ffb454")];
+pub static DEFAULT: &[(CssFile<'static>, Item, Value, ValueType)] =
+    &[/*                   pagetoc related                                 */
+      default!(Variables, "sidebar-width",             "140px",   Length),
+      default!(Variables, "page-padding",              "15px",    Length),
+      default!(Variables, "content-max-width",         "82%",     Length),
+      default!(Variables, "menu-bar-height",           "40px",    Length),
+      default!(Variables, "pagetoc-width",             "13%",     Length),
+      default!(Variables, "pagetoc-fontsize",          "14.5px",  FontSize),
+      default!(Variables, "mobile-content-max-width",  "98%",     Length),
+      default!(General,   "content-padding",           "0 10px",  Shorthand),
+      default!(General,   "content-main-margin-left",  "2%",      Length),
+      default!(General,   "content-main-margin-right", "2%",      Length),
+      default!(Chrome,    "nav-chapters-max-width",    "auto",    LengthOrAuto),
+      default!(Chrome,    "nav-chapters-min-width",    "auto",    LengthOrAuto),
+      default!(Chrome,    "chapter-line-height",       "2em",     LineHeight),
+      default!(Chrome,    "section-line-height",       "1.5em",   LineHeight),
+      /*                   font-size related                               */
+      default!(General,   "root-font-size",            "70%",     FontSize),
+      default!(General,   "body-font-size",            "1.5rem",  FontSize),
+      default!(General,   "code-font-size",            "0.9em",   FontSize),
+      default!(Chrome,    "sidebar-font-size",         "1em",     FontSize),
+      /*                   font-family related: written only when set      */
+      default!(General,   "body-font-family",          BODY_FONT, FontFamily),
+      default!(Variables, "code-font-family",          MONO_FONT, FontFamily),
+      /*                   color related                                   */
+      default!(Variables, "light-links",               "#1f1fff", Color),
+      default!(Variables, "light-inline-code-color",   "#F42C4C", Color),
+      default!(Variables, "rust-links",                "#2b79a2", Color),
+      default!(Variables, "rust-inline-code-color",    "#6e6b5e", Color),
+      default!(Variables, "navy-links",                "#2b79a2", Color),
+      default!(Variables, "navy-inline-code-color",    "#c5c8c6", Color),
+      default!(Variables, "coal-links",                "#2b79a2", Color),
+      default!(Variables, "coal-inline-code-color",    "#c5c8c6", Color),
+      default!(Variables, "ayu-links",                 "#0096cf", Color),
+      default!(Variables, "ayu-inline-code-color",     "#ffb454", Color)];
+
+/// mdBook's own stylesheets, which are left untouched when `override = true` .
+#[rustfmt::skip]
+pub static MDBOOK_CSS: &[(&str, &[u8])] =
+    &[("css/variables.css",  mdbook::theme::VARIABLES_CSS),
+      ("css/general.css",    mdbook::theme::GENERAL_CSS),
+      ("css/chrome.css",     mdbook::theme::CHROME_CSS),
+      ("css/print.css",      mdbook::theme::PRINT_CSS),
+      ("highlight.css",      mdbook::theme::HIGHLIGHT_CSS),
+      ("tomorrow-night.css", mdbook::theme::TOMORROW_NIGHT_CSS),
+      ("ayu-highlight.css",  mdbook::theme::AYU_HIGHLIGHT_CSS)];
+
+/// The other files of mdBook's theme a theme dir may have a copy of, checked by `doctor` .
+#[rustfmt::skip]
+pub static MDBOOK_OTHERS: &[(&str, &[u8])] =
+    &[("index.hbs",                            mdbook::theme::INDEX),
+      ("head.hbs",                             mdbook::theme::HEAD),
+      ("header.hbs",                           mdbook::theme::HEADER),
+      ("redirect.hbs",                         mdbook::theme::REDIRECT),
+      ("toc.js.hbs",                           mdbook::theme::TOC_JS),
+      ("toc.html.hbs",                         mdbook::theme::TOC_HTML),
+      ("book.js",                              mdbook::theme::JS),
+      ("highlight.js",                         mdbook::theme::HIGHLIGHT_JS),
+      ("clipboard.min.js",                     mdbook::theme::CLIPBOARD_JS),
+      ("favicon.svg",                          mdbook::theme::FAVICON_SVG),
+      ("favicon.png",                          mdbook::theme::FAVICON_PNG),
+      ("FontAwesome/css/font-awesome.min.css", mdbook::theme::FONT_AWESOME)];
+
+/// Themes of mdBook, whose ids are listed in the theme picker of `index.hbs` .
+pub static BUILTIN_THEMES: &[&str] = &["light", "rust", "coal", "navy", "ayu"];
+
+/// Items in `[preprocessor.theme]` that are options of this tool rather than css values.
+pub static OPTIONS: &[&str] = &[
+    "turn-off",
+    "pagetoc",
+    "strict",
+    "dry-run",
+    "override",
+    "css",
+    "colors",
+    "custom-themes",
+    "palette",
+    "px-to-rem",
+    "contrast",
+    "contrast-strict",
+    "fonts",
+    "strings",
+];
+
+/// Keys in `[preprocessor.theme]` that belong to mdbook itself.
+pub static MDBOOK_KEYS: &[&str] = &["command", "renderers", "before", "after", "optional"];
 
 pub static PAGETOCCSS: &[u8] = include_bytes!("pagetoc.css");
 pub static PAGETOCJS: &[u8] = include_bytes!("pagetoc.js");
@@ -0,0 +1,227 @@
+//! `[preprocessor.theme.fonts]` : copy local font files into `fonts/` of the theme dir, and
+//! declare them with `@font-face` rules in `fonts/fonts.css` .
+//!
+//! mdBook only copies its own fonts when the theme dir has no `fonts/fonts.css` , so extending
+//! mdBook's stylesheet copies its fonts as well.
+
+use super::output::Output;
+use crate::{Error, Result};
+use mdbook::theme::fonts;
+use std::path::{Path, PathBuf};
+
+/// the stylesheet of fonts in the theme dir
+pub const FONTS_CSS: &str = "fonts/fonts.css";
+
+/// where the generated `@font-face` rules are in `fonts/fonts.css`
+const BEGIN: &str = "/* mdbook-theme: fonts from [preprocessor.theme.fonts] */";
+const END: &str = "/* mdbook-theme: end of fonts */";
+
+/// font weights in file names, like `Inter-SemiBold.woff2`
+const WEIGHTS: &[(&str, u16)] = &[
+    ("thin", 100),
+    ("hairline", 100),
+    ("extralight", 200),
+    ("ultralight", 200),
+    ("light", 300),
+    ("regular", 400),
+    ("normal", 400),
+    ("book", 400),
+    ("medium", 500),
+    ("semibold", 600),
+    ("demibold", 600),
+    ("bold", 700),
+    ("extrabold", 800),
+    ("ultrabold", 800),
+    ("black", 900),
+    ("heavy", 900),
+];
+
+/// A font file and how it's declared in `@font-face` .
+#[derive(Clone, Debug)]
+pub struct Font {
+    /// the file in the book
+    pub path: PathBuf,
+    pub family: String,
+    pub weight: String,
+    pub style: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Fonts {
+    pub files: Vec<Font>,
+    /// `mode = "replace"` : drop mdBook's fonts instead of extending its `fonts/fonts.css`
+    pub replace: bool,
+}
+
+impl Font {
+    /// Guess the family, weight and style from the file name, e.g. `Inter-BoldItalic.woff2` is
+    /// `Inter` in bold (700) italic, and `Source-Code-Pro.ttf` is `Source Code Pro` .
+    pub fn from_path(path: PathBuf) -> std::result::Result<Self, String> {
+        format(&path)?;
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        let stem = stem.ok_or_else(|| format!("`{}` is not a file", path.display()))?;
+        let mut words: Vec<_> = stem
+            .split(['-', '_', ' '])
+            .filter(|w| !w.is_empty())
+            .collect();
+        let (mut weight, mut style) = (400, "normal");
+        while words.len() > 1 {
+            let last = words[words.len() - 1].to_ascii_lowercase();
+            let (rest, italic) = match last.strip_suffix("italic") {
+                Some(rest) => (rest, true),
+                None => (last.as_str(), false),
+            };
+            let w = match rest {
+                "" => None,
+                r => (WEIGHTS.iter().find(|(name, _)| *name == r).map(|(_, w)| *w))
+                    .or_else(|| r.parse().ok().filter(|w| (1..=1000).contains(w))),
+            };
+            if w.is_none() && !rest.is_empty() {
+                break; // a part of the family
+            }
+            weight = w.unwrap_or(weight);
+            if italic {
+                style = "italic";
+            }
+            words.pop();
+        }
+        Ok(Font {
+            family: words.join(" "),
+            weight: weight.to_string(),
+            style: style.to_string(),
+            path,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map_or_else(String::new, |f| f.to_string_lossy().into_owned())
+    }
+
+    /// an `@font-face` rule in mdBook's style
+    fn face(&self) -> String {
+        let file = self.file_name();
+        let format = format(&self.path).unwrap_or_default();
+        format!(
+            "@font-face {{\n  font-family: \"{}\";\n  font-style: {};\n  font-weight: {};\n  \
+             src: url('{file}') format('{format}');\n}}\n",
+            self.family.replace('"', "\\\""),
+            self.style,
+            self.weight
+        )
+    }
+}
+
+/// the `format()` of a font file
+fn format(path: &Path) -> std::result::Result<&'static str, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("woff2") => Ok("woff2"),
+        Some("woff") => Ok("woff"),
+        Some("ttf") => Ok("truetype"),
+        Some("otf") => Ok("opentype"),
+        _ => Err(format!(
+            "`{}` is not a font file: expected .woff2, .woff, .ttf or .otf",
+            path.display()
+        )),
+    }
+}
+
+impl Fonts {
+    /// Copy the font files into the theme dir, and write the `@font-face` rules.
+    pub fn write(&self, dir: &Path, output: &mut Output) -> Result<()> {
+        let css_path = dir.join(FONTS_CSS);
+        let base = if self.replace {
+            String::from("/* Generated by mdbook-theme: mdBook's fonts are replaced. */\n")
+        } else if output.exists(&css_path) {
+            output.read(&css_path)?
+        } else {
+            // mdBook no longer copies its fonts once `fonts/fonts.css` is in the theme dir
+            let stock = fonts::LICENSES.iter().chain(fonts::OPEN_SANS.iter());
+            for (name, bytes) in stock.chain(std::iter::once(&fonts::SOURCE_CODE_PRO)) {
+                output.write_bytes(&dir.join(name), bytes)?;
+            }
+            String::from_utf8_lossy(fonts::CSS).into_owned()
+        };
+
+        let mut faces = String::new();
+        for font in &self.files {
+            let bytes =
+                std::fs::read(&font.path).map_err(|e| Error::FileNotRead(font.path.clone(), e))?;
+            output.write_bytes(&dir.join("fonts").join(font.file_name()), &bytes)?;
+            faces.push_str(&format!("\n{}", font.face()));
+        }
+        let css = with_faces(&base, &faces);
+        if !output.exists(&css_path) || output.read(&css_path)? != css {
+            output.write(&css_path, &css)?;
+        }
+        Ok(())
+    }
+}
+
+/// Put `faces` between the markers at the end of `css` , replacing the ones generated before.
+fn with_faces(css: &str, faces: &str) -> String {
+    let mut css = match (css.find(BEGIN), css.find(END)) {
+        (Some(begin), Some(end)) if begin < end => {
+            format!("{}{}", &css[..begin], css[end + END.len()..].trim_start())
+        }
+        _ => css.to_string(),
+    };
+    let trimmed = css.trim_end().len();
+    css.truncate(trimmed);
+    format!("{css}\n\n{BEGIN}\n{faces}{END}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_faces, Font, BEGIN, END};
+    use std::path::PathBuf;
+
+    #[test]
+    fn guess_from_file_names() {
+        #[rustfmt::skip]
+        let cases = [
+            ("fonts/Inter-Regular.woff2",         "Inter",           "400", "normal"),
+            ("Inter-BoldItalic.woff2",            "Inter",           "700", "italic"),
+            ("Inter-Italic.ttf",                  "Inter",           "400", "italic"),
+            ("Inter-SemiBold.otf",                "Inter",           "600", "normal"),
+            ("Inter_ExtraLight_Italic.woff",      "Inter",           "200", "italic"),
+            ("Source-Code-Pro.ttf",               "Source Code Pro", "400", "normal"),
+            ("Noto Sans SC 300.woff2",            "Noto Sans SC",    "300", "normal"),
+            ("JetBrainsMono-900italic.woff2",     "JetBrainsMono",   "900", "italic"),
+            ("Black.woff2",                       "Black",           "400", "normal"),
+        ];
+        for (path, family, weight, style) in cases {
+            let font = Font::from_path(PathBuf::from(path)).unwrap();
+            let got = (
+                font.family.as_str(),
+                font.weight.as_str(),
+                font.style.as_str(),
+            );
+            assert_eq!(got, (family, weight, style), "{}", path);
+        }
+    }
+
+    #[test]
+    fn only_font_files() {
+        assert!(Font::from_path(PathBuf::from("Inter.WOFF2")).is_ok());
+        for path in ["Inter.css", "Inter", "fonts/"] {
+            assert!(Font::from_path(PathBuf::from(path)).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn faces_replace_the_generated_ones() {
+        let css = "@font-face { font-family: mdBook; }\n";
+        let once = with_faces(css, "\nA\n");
+        assert_eq!(once, format!("{}\n\n{BEGIN}\n\nA\n{END}\n", css.trim_end()));
+        let twice = with_faces(&once, "\nB\n");
+        assert_eq!(
+            twice,
+            format!("{}\n\n{BEGIN}\n\nB\n{END}\n", css.trim_end())
+        );
+    }
+}
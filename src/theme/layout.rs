@@ -0,0 +1,114 @@
+//! Anchors in mdBook's theme files that this tool edits around, per layout of mdBook's theme.
+//!
+//! The layout is chosen by the version of mdBook calling the preprocessor (`mdbook_version` in
+//! its input). The copies of mdBook's theme files in this tool are from the mdBook it's built
+//! against, so with another layout, the theme files have to be in the theme dir.
+
+use super::default::MDBOOK_CSS;
+use crate::{Error, Result};
+use semver::Version;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// the mdBook release the layout is known as
+    pub name: &'static str,
+    /// the major and minor version of the mdBook releases with this layout, prereleases included
+    pub version: (u64, u64),
+    /// `index.hbs` : the pagetoc is put between them
+    pub main: &'static str,
+    pub content: &'static str,
+    /// `index.hbs` : the list of the theme picker, and the prefix of the ids of its buttons
+    pub theme_list: &'static str,
+    pub theme_id: &'static str,
+    /// the id of the menu bar, which the pagetoc keeps below
+    pub menu_bar: &'static str,
+}
+
+pub const LAYOUTS: &[Layout] = &[
+    Layout {
+        name: "0.4",
+        version: (0, 4),
+        main: "<main>",
+        content: "{{{ content }}}",
+        theme_list: r#"id="theme-list""#,
+        theme_id: "",
+        menu_bar: "menu-bar",
+    },
+    // html ids are prefixed with `mdbook-`
+    Layout {
+        name: "0.5",
+        version: (0, 5),
+        main: "<main>",
+        content: "{{{ content }}}",
+        theme_list: r#"id="mdbook-theme-list""#,
+        theme_id: "mdbook-theme-",
+        menu_bar: "mdbook-menu-bar",
+    },
+];
+
+impl Default for &'static Layout {
+    fn default() -> Self {
+        Layout::bundled()
+    }
+}
+
+impl Layout {
+    /// The layout for a version of mdBook, like `0.4.43` or `0.5.0-alpha.1` .
+    pub fn detect(version: &str) -> Result<&'static Layout> {
+        let unsupported = || Error::UnsupportedMdbook(version.to_string());
+        let version = Version::parse(version).map_err(|_| unsupported())?;
+        LAYOUTS
+            .iter()
+            .find(|l| l.version == (version.major, version.minor))
+            .ok_or_else(unsupported)
+    }
+
+    /// The layout of the theme files in this tool.
+    pub fn bundled() -> &'static Layout {
+        Layout::detect(mdbook::MDBOOK_VERSION).unwrap_or(&LAYOUTS[0])
+    }
+
+    /// A theme file of mdBook missing in the theme dir can only be made from the copy in this
+    /// tool with the same layout.
+    pub fn check_missing(&self, filename: &str, path: &Path) -> Result<()> {
+        let stock = filename == "index.hbs" || MDBOOK_CSS.iter().any(|(f, _)| *f == filename);
+        if stock && self != Layout::bundled() {
+            return Err(Error::NoThemeFile {
+                file: path.to_path_buf(),
+                mdbook: self.name,
+                bundled: Layout::bundled().name,
+            });
+        }
+        Ok(())
+    }
+
+    /// The anchors should be in a theme file, or it's not in this layout.
+    pub fn check(&self, text: &str, anchors: &[&str], path: &Path) -> Result<()> {
+        match anchors.iter().find(|a| !text.contains(*a)) {
+            Some(anchor) => Err(Error::LayoutMismatch {
+                file: path.to_path_buf(),
+                anchor: anchor.to_string(),
+                mdbook: self.name,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+
+    #[test]
+    fn detect_by_major_and_minor() {
+        let name = |version| Layout::detect(version).map(|l| l.name).ok();
+        assert_eq!(name("0.4.43"), Some("0.4"));
+        assert_eq!(name("0.4.50-rc.1"), Some("0.4"));
+        assert_eq!(name("0.5.0-alpha.1"), Some("0.5"));
+        assert_eq!(name("0.5.2"), Some("0.5"));
+        assert_eq!(name("0.3.7"), None);
+        assert_eq!(name("0.6.0"), None);
+        assert_eq!(name("not a version"), None);
+    }
+}
@@ -0,0 +1,286 @@
+//! `.mdbook-theme.json` in the theme dir records the files this tool created or modified,
+//! so that `mdbook-theme clean` and `mdbook-theme restore` can undo them later.
+//!
+//! A file that existed before this tool first touched it is backed up under
+//! `.mdbook-theme-backup/` in the theme dir.
+
+use crate::{Error, Result};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST: &str = ".mdbook-theme.json";
+pub const BACKUP: &str = ".mdbook-theme-backup";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// the theme dir
+    #[serde(skip)]
+    dir: PathBuf,
+    /// paths relative to the theme dir
+    files: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Entry {
+    /// sha256 of the file before this tool first touched it; `null` if created by this tool
+    original: Option<String>,
+    /// sha256 of the content this tool wrote last time
+    written: String,
+    /// the version of mdBook whose theme file a created file was made from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mdbook: Option<String>,
+}
+
+impl Manifest {
+    /// Load the manifest in the theme dir, or start an empty one.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        let mut manifest: Manifest = if path.exists() {
+            let json =
+                fs::read_to_string(&path).map_err(|e| Error::FileNotRead(path.clone(), e))?;
+            serde_json::from_str(&json).map_err(|e| Error::ManifestNotParsed(path, e.into()))?
+        } else {
+            Manifest::default()
+        };
+        manifest.dir = dir.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Record `content` is about to be written into `path` : back up the file on first touch.
+    pub fn record(&mut self, path: &Path, content: &[u8]) -> Result<()> {
+        let name = match path.strip_prefix(&self.dir) {
+            Ok(p) => p.to_string_lossy().replace('\\', "/"),
+            Err(_) => return Ok(()), // not in the theme dir
+        };
+        let written = hash(content);
+        match self.files.get_mut(&name) {
+            Some(entry) => entry.written = written,
+            None => {
+                let (original, mdbook) = if path.exists() {
+                    copy(path, &self.dir.join(BACKUP).join(&name))?;
+                    (Some(hash(&read(path)?)), None)
+                } else {
+                    (None, Some(mdbook::MDBOOK_VERSION.to_string()))
+                };
+                let entry = Entry {
+                    original,
+                    written,
+                    mdbook,
+                };
+                self.files.insert(name, entry);
+            }
+        }
+        self.save() // saved on every record, so a failed build won't lose any backup
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.dir.join(MANIFEST);
+        if self.files.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| Error::FileNotRemoved(path, e))?;
+            }
+            let backup = self.dir.join(BACKUP);
+            if backup.exists() {
+                fs::remove_dir_all(&backup).map_err(|e| Error::DirNotRemoved(backup, e))?;
+            }
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(self).expect("a manifest is always serializable");
+        fs::write(&path, json + "\n").map_err(|e| Error::FileNotWritten(path, e))
+    }
+
+    /// How this tool touched the file `name` (relative to the theme dir), if it did.
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.files.get(name)
+    }
+
+    /// the recorded files, relative to the theme dir
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// The backup of a file existing before this tool first modified it.
+    pub fn backup(&self, name: &str) -> PathBuf {
+        self.dir.join(BACKUP).join(name)
+    }
+
+    /// Remove the files created by this tool. Modified files are kept as they are.
+    /// Returns what is done (or skipped) for each file.
+    pub fn clean(&mut self, force: bool) -> Result<Vec<String>> {
+        self.undo(force, false)
+    }
+
+    /// Remove the files created by this tool and bring back the original ones from backups.
+    /// Returns what is done (or skipped) for each file.
+    pub fn restore(&mut self, force: bool) -> Result<Vec<String>> {
+        self.undo(force, true)
+    }
+
+    fn undo(&mut self, force: bool, restore: bool) -> Result<Vec<String>> {
+        let mut report = Vec::new();
+        let mut done = Vec::new();
+        for (name, entry) in &self.files {
+            if entry.original.is_some() && !restore {
+                continue;
+            }
+            let path = self.dir.join(name);
+            if path.exists() && hash(&read(&path)?) != entry.written && !force {
+                report.push(format!(
+                    "skipped `{name}`: changed since mdbook-theme wrote it (use --force)"
+                ));
+                continue;
+            }
+            let backup = self.dir.join(BACKUP).join(name);
+            if entry.original.is_some() && !backup.exists() {
+                report.push(format!("skipped `{name}`: the backup is missing"));
+                continue;
+            } else if entry.original.is_some() {
+                copy(&backup, &path)?;
+                report.push(format!("restored `{name}`"));
+            } else if path.exists() {
+                fs::remove_file(&path).map_err(|e| Error::FileNotRemoved(path.clone(), e))?;
+                self.remove_empty_dirs(&path);
+                report.push(format!("removed `{name}`"));
+            }
+            done.push(name.clone());
+        }
+        for name in done {
+            self.files.remove(&name);
+        }
+        self.save()?;
+        if self.files.is_empty() {
+            self.remove_empty_dirs(&self.dir.join(MANIFEST));
+        }
+        Ok(report)
+    }
+
+    /// Remove the parent dirs of `path` left empty, up to and including the theme dir.
+    fn remove_empty_dirs(&self, path: &Path) {
+        let empty = |dir: &Path| fs::read_dir(dir).is_ok_and(|mut d| d.next().is_none());
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.dir) || !empty(dir) || fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Entry {
+    /// Whether `content` is what this tool wrote last time.
+    pub fn is_written(&self, content: &[u8]) -> bool {
+        hash(content) == self.written
+    }
+
+    /// Whether the file was created by this tool, rather than modified.
+    pub fn is_created(&self) -> bool {
+        self.original.is_none()
+    }
+
+    /// The version of mdBook whose theme file a created file was made from; `None` if modified,
+    /// or recorded by an earlier version of this tool.
+    pub fn mdbook(&self) -> Option<&str> {
+        self.mdbook.as_deref()
+    }
+}
+
+pub fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| Error::FileNotRead(path.to_path_buf(), e))
+}
+
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| Error::DirNotCreated(parent.to_path_buf(), e.into()))?;
+    }
+    fs::copy(from, to)
+        .map(drop)
+        .map_err(|e| Error::FileNotWritten(to.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, BACKUP, MANIFEST};
+    use crate::test_dir;
+    use std::{fs, path::Path};
+
+    /// what `Output` does for a file
+    fn write(manifest: &mut Manifest, path: &Path, content: &str) {
+        manifest.record(path, content.as_bytes()).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn clean_removes_created_files() {
+        let dir = test_dir("manifest-clean");
+        let (created, modified) = (dir.join("css/new.css"), dir.join("old.css"));
+        fs::write(&modified, "mine").unwrap();
+        let mut manifest = Manifest::load(&dir).unwrap();
+        write(&mut manifest, &created, "by the tool");
+        write(&mut manifest, &modified, "by the tool");
+
+        let mut manifest = Manifest::load(&dir).unwrap();
+        assert!(manifest.entry("css/new.css").unwrap().is_created());
+        assert!(!manifest.entry("old.css").unwrap().is_created());
+        let report = manifest.clean(false).unwrap();
+        assert_eq!(report, ["removed `css/new.css`"]);
+        assert!(!created.exists() && !dir.join("css").exists());
+        assert_eq!(
+            read(&modified),
+            "by the tool",
+            "modified files are kept by clean"
+        );
+        assert_eq!(manifest.names().collect::<Vec<_>>(), ["old.css"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_brings_back_the_backup() {
+        let dir = test_dir("manifest-restore");
+        let (created, modified) = (dir.join("new.css"), dir.join("old.css"));
+        fs::write(&modified, "mine").unwrap();
+        let mut manifest = Manifest::load(&dir).unwrap();
+        write(&mut manifest, &modified, "by the tool");
+        write(&mut manifest, &modified, "by the tool again");
+        write(&mut manifest, &created, "by the tool");
+        assert_eq!(
+            read(&manifest.backup("old.css")),
+            "mine",
+            "backed up on first touch"
+        );
+
+        // edited by hand since: skipped without --force
+        fs::write(&created, "edited").unwrap();
+        fs::write(&modified, "edited").unwrap();
+        let mut manifest = Manifest::load(&dir).unwrap();
+        let report = manifest.restore(false).unwrap();
+        assert_eq!(report.len(), 2);
+        assert!(
+            report.iter().all(|r| r.starts_with("skipped")),
+            "{:?}",
+            report
+        );
+        assert_eq!(read(&modified), "edited");
+
+        let report = manifest.restore(true).unwrap();
+        assert_eq!(report, ["removed `new.css`", "restored `old.css`"]);
+        assert_eq!(read(&modified), "mine");
+        assert!(!created.exists());
+        assert!(!dir.join(MANIFEST).exists() && !dir.join(BACKUP).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
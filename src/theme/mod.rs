@@ -1,13 +1,33 @@
 use crate::{Error, Result};
+use css::{StyleRule, Stylesheet};
 use default::*;
+use layout::Layout;
+use output::Output;
+use pagetoc::Pagetoc;
+use palette::Palette;
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::fmt;
 use std::hash::Hash;
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+pub mod color;
 pub mod config;
+pub mod contrast;
+pub mod convert;
+pub mod css;
 pub mod default;
+pub mod fonts;
+pub mod layout;
+pub mod manifest;
+pub mod output;
+pub mod overrides;
+pub mod pagetoc;
+pub mod palette;
+pub mod strings;
+pub mod units;
 
 /// All cssfiles to be modified.
 /// There are several aspects of configs:
@@ -18,14 +38,14 @@ pub mod default;
 /// but in practice all configs are processed in unit of single file.
 #[rustfmt::skip]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum CssFile {
+pub enum CssFile<'a> {
     Variables, General, Chrome, Index, PagetocJs, PagetocCss,
-    Invalid, Pagetoc, Custom(&'static str)
+    Invalid, Pagetoc, Custom(&'a str)
 }
 
-impl CssFile {
+impl<'a> CssFile<'a> {
     /// get filename according to `CssFile` type
-    pub fn filename(&self) -> &'static str {
+    pub fn filename(&self) -> &'a str {
         if let CssFile::Custom(filename) = self {
             filename
         } else {
@@ -33,9 +53,17 @@ impl CssFile {
         }
     }
 
-    /// get `CssFile` variant according to filename
-    pub fn variant(filename: &str) -> Self {
-        CSSFILES.iter().find(|&(_, f)| &filename == f).unwrap().0
+    /// get `CssFile` variant according to filename: a file unknown to this tool is `Custom`
+    pub fn variant(filename: &'a str) -> Self {
+        CSSFILES
+            .iter()
+            .find(|&(_, f)| &filename == f)
+            .map_or(CssFile::Custom(filename), |&(css, _)| css)
+    }
+
+    /// whether the file is a stylesheet
+    pub fn is_css(&self) -> bool {
+        self.filename().ends_with(".css")
     }
 }
 
@@ -79,6 +107,26 @@ impl fmt::Debug for Value<'_> {
     }
 }
 
+/// What an item in [`DEFAULT`] takes, to check values from book.toml.
+///
+/// [`DEFAULT`]: ./default/static.DEFAULT.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    /// a length or a percentage, like `140px` or `82%`
+    Length,
+    /// a length, a percentage, `auto` or `none`
+    LengthOrAuto,
+    /// 1 to 4 lengths or percentages, like `0 10px` for `padding`
+    Shorthand,
+    /// `normal` , a number, a length or a percentage
+    LineHeight,
+    /// a length, a percentage or a keyword like `small`
+    FontSize,
+    /// comma-separated font names, like `Inter, sans-serif`
+    FontFamily,
+    Color,
+}
+
 /// configs ready to go
 #[derive(Clone, Default)]
 pub struct Ready<'a>(Vec<(Item<'a>, Value<'a>)>);
@@ -113,29 +161,74 @@ impl Ready<'_> {
     ///
     /// [`DEFAULT`]: ./default/static.DEFAULT.html
     #[rustfmt::skip]
-    pub fn get_defualt(css: CssFile) -> Self {
+    pub fn get_defualt(css: CssFile<'_>) -> Self {
         match css {
             c @ CssFile::Variables => Ready::from(c),
             c @ CssFile::General   => Ready::from(c),
             c @ CssFile::Chrome    => Ready::from(c),
+            CssFile::Index         => Ready(vec![(Item("pagetoc"), Value("true"))]),
             _                      => Self::default(),
         }
     }
 
-    /// help to simplify `.get()`
-    fn from(css: CssFile) -> Self {
+    /// help to simplify `.get()` : font families are mdBook's already, and left as they are
+    /// unless set, so that a customized font isn't reset
+    fn from(css: CssFile<'_>) -> Self {
         DEFAULT
             .iter()
-            .filter(|(c, _, _)| *c == css)
-            .map(|(_, i, v)| (*i, *v))
+            .filter(|(c, _, _, ty)| *c == css && *ty != ValueType::FontFamily)
+            .map(|(_, i, v, _)| (*i, *v))
             .collect()
     }
 
-    pub fn item_value(&self) -> &Vec<(Item, Value)> {
+    pub fn item_value(&self) -> &Vec<(Item<'_>, Value<'_>)> {
+        &self.0
+    }
+}
+
+/// A declaration from `[preprocessor.theme.css."file"."selector"]` in book.toml,
+/// or `[preprocessor.theme.css."file"."@media ..."."selector"]` if it's in an at-rule.
+#[derive(Clone, Copy, Debug)]
+pub struct Declare<'a> {
+    pub at_rule: Option<&'a str>,
+    pub selector: &'a str,
+    pub property: &'a str,
+    pub value: Value<'a>,
+}
+
+/// declarations ready to go
+#[derive(Clone, Default, Debug)]
+pub struct Declares<'a>(Vec<Declare<'a>>);
+
+impl<'a> Declares<'a> {
+    pub fn push(&mut self, declare: Declare<'a>) {
+        self.0.push(declare);
+    }
+
+    pub fn get(&self) -> &Vec<Declare<'a>> {
         &self.0
     }
 }
 
+/// A colour variable from `[preprocessor.theme.colors.theme]` in book.toml, such as
+/// `sidebar-bg` for `--sidebar-bg` in `.navy` .
+#[derive(Clone, Copy, Debug)]
+pub struct Color<'a> {
+    pub theme: &'a str,
+    pub name: &'a str,
+    pub value: Value<'a>,
+}
+
+/// A new theme from `[preprocessor.theme.custom-themes.name]` in book.toml: a copy of the
+/// variables of `base` with some of them changed, listed as `label` in the theme picker.
+#[derive(Clone, Debug)]
+pub struct CustomTheme<'a> {
+    pub name: &'a str,
+    pub label: String,
+    pub base: &'a str,
+    pub colors: Vec<(&'a str, Value<'a>)>,
+}
+
 #[derive(Clone, PartialEq, Default)]
 pub struct Content(String);
 
@@ -154,35 +247,28 @@ impl fmt::Debug for Content {
 impl Content {
     /// All contents that are to modify or directly use.
     #[rustfmt::skip]
-    pub fn from(cssfile: CssFile, dir: &Path) -> Self {
+    pub fn from(cssfile: CssFile<'_>, dir: &Path, output: &Output) -> Result<Self> {
         use mdbook::theme::*;
-        match cssfile {
-            CssFile::Custom(f)  => Content::from_file(dir, f),
+        Ok(match cssfile {
+            CssFile::Custom(f) if output.exists(&dir.join(f))
+                                => Content(output.read(&dir.join(f))?),
             CssFile::Variables  => Content::from_static(VARIABLES_CSS),
             CssFile::Index      => Content::from_static(INDEX),
             CssFile::PagetocJs  => Content::from_static(PAGETOCJS),
             CssFile::PagetocCss => Content::from_static(PAGETOCCSS),
             CssFile::Chrome     => Content::from_static(CHROME_CSS),
             CssFile::General    => Content::from_static(GENERAL_CSS),
+            CssFile::Custom(f)  => MDBOOK_CSS.iter().find(|(name, _)| *name == f)
+                                             .map_or_else(Content::default,
+                                                          |(_, v)| Content::from_static(v)),
             _                   => Content::default(),
-        }
+        })
     }
 
     fn from_static(v: &[u8]) -> Self {
         Content(String::from(unsafe { std::str::from_utf8_unchecked(v) }))
     }
 
-    fn from_file(dir: &Path, filename: &str) -> Self {
-        use std::fs::File;
-        use std::io::Read;
-        let mut s = String::new();
-        File::open(dir.join(filename))
-            .unwrap()
-            .read_to_string(&mut s)
-            .unwrap();
-        Content(s)
-    }
-
     /// for viewing the content
     pub fn get(&self) -> &str {
         &self.0
@@ -193,84 +279,315 @@ impl Content {
         &mut self.0
     }
 
-    /// Update the content: directly relapce a value.
-    /// Useful when the item is exact or replace the first one.
-    ///
-    /// Hypothesis: `item: value;` .
-    /// Better to use [`regex`](https://docs.rs/regex/*/regex/), but for now I'm not ready :(
-    fn replace(&mut self, item: &str, value: &str) -> Result<()> {
-        let text = self.get();
-        let p1 = text.find(item).ok_or(Error::StrNotFound)? + item.len() + 2;
-        let p2 = p1 + text[p1..].find(';').ok_or(Error::StrNotFound)?;
-        self.get_mut().replace_range(p1..p2, value);
-        // eprintln!("\n{}", &self.get()[p1 - 20..p2 + 10]);
+    /// Parse the content as a stylesheet.
+    pub fn stylesheet(&self) -> Stylesheet {
+        Stylesheet::parse(self.get())
+    }
+
+    /// Update the content: set `property: value;` in the first top-level rule of `selector`.
+    /// If the rule has no such property, the declaration is appended to the rule.
+    fn set(&mut self, selector: &str, property: &str, value: &str) -> Result<()> {
+        let sheet = self.stylesheet();
+        let rule = sheet
+            .rule(selector, Some(property))
+            .ok_or_else(|| Error::StrNotFound(format!("selector `{selector}`")))?;
+        self.set_in_rule(rule, property, value);
         Ok(())
     }
 
-    /// update the content with information of context:
-    /// it's common to see homonymous args items in different context,
-    /// so this function takes an additional foregoing hint (need two locations).
-    fn fore_replace(&mut self, fore: &str, item: &str, value: &str) -> Result<()> {
+    /// Replace the value if `rule` declares `property` , or append the declaration.
+    fn set_in_rule(&mut self, rule: &StyleRule, property: &str, value: &str) {
+        if let Some(decl) = rule.declaration(property) {
+            self.get_mut().replace_range(decl.value.clone(), value);
+            return;
+        }
         let text = self.get();
-        let pfore = text.find(fore).ok_or(Error::StrNotFound)?;
-        let p1 = text[pfore..].find(item).ok_or(Error::StrNotFound)? + pfore + item.len() + 2;
-        let p2 = p1 + text[p1..].find(';').ok_or(Error::StrNotFound)?;
-        self.get_mut().replace_range(p1..p2, value);
+        let block = &text[rule.block.clone()];
+        // append right after the last non-blank char in the block to keep trailing comments
+        let pos = rule.block.start + block.trim_end().len();
+        match rule.declarations.last() {
+            Some(last) => {
+                let line_start = text[..last.span.start].rfind('\n').map_or(0, |p| p + 1);
+                let indent = &text[line_start..last.span.start];
+                let indent = if indent.trim().is_empty() {
+                    indent
+                } else {
+                    "    "
+                };
+                let insert = format!("\n{indent}{property}: {value};");
+                let value_end = last.value.end;
+                let terminated = last.terminated;
+                self.get_mut().insert_str(pos, &insert);
+                if !terminated {
+                    self.get_mut().insert(value_end, ';');
+                }
+            }
+            None if block.trim().is_empty() => {
+                let insert = format!("\n    {property}: {value};\n");
+                self.get_mut().replace_range(rule.block.clone(), &insert);
+            }
+            None => self
+                .get_mut()
+                .insert_str(pos, &format!("\n    {property}: {value};")),
+        }
+    }
+
+    /// Like [`Content::set`], but create the rule at the end of the stylesheet if not found.
+    fn set_rule(&mut self, selector: &str, property: &str, value: &str) -> Result<()> {
+        if self.set(selector, property, value).is_ok() {
+            return Ok(());
+        }
+        let text = self.get_mut();
+        let sep = if text.trim().is_empty() { "" } else { "\n\n" };
+        text.truncate(text.trim_end().len());
+        text.push_str(&format!(
+            "{sep}{selector} {{\n    {property}: {value};\n}}\n"
+        ));
+        Ok(())
+    }
+
+    /// Set `property: value;` for `selector` inside the top-level at-rule `prelude` .
+    /// If the at-rule doesn't exist, it'll be created right after the top-level rule of `after`,
+    /// or at the end of the stylesheet if `after` is `None` .
+    fn set_in_at_rule(
+        &mut self,
+        prelude: &str,
+        after: Option<&str>,
+        selector: &str,
+        property: &str,
+        value: &str,
+    ) -> Result<()> {
+        let sheet = self.stylesheet();
+        if let Some(at) = sheet.at_rule(prelude) {
+            if let Some(rule) = at.rule(selector, Some(property)) {
+                self.set_in_rule(rule, property, value);
+                return Ok(());
+            }
+            let block = at
+                .block
+                .clone()
+                .ok_or_else(|| Error::StrNotFound(format!("block of `{prelude}`")))?;
+            let pos = block.start + self.get()[block].trim_end().len();
+            let insert = format!("\n    {selector} {{\n        {property}: {value};\n    }}");
+            self.get_mut().insert_str(pos, &insert);
+            return Ok(());
+        }
+        let pos = match after {
+            Some(after) => {
+                let rule = sheet.rule(after, None);
+                rule.ok_or_else(|| Error::StrNotFound(format!("selector `{after}`")))?
+                    .span
+                    .end
+            }
+            None => self.get().trim_end().len(),
+        };
+        let insert = format!(
+            "{prelude} {{
+    {selector} {{
+        {property}: {value};
+    }}
+}}"
+        );
+        if pos == 0 {
+            // a new or empty file: no blank lines before
+            *self.get_mut() = insert + "\n";
+        } else {
+            self.get_mut().insert_str(pos, &format!("\n\n{insert}"));
+        }
         Ok(())
     }
 
     /// Insert content, and need two str to find.
     /// The first is to find backwards;
     /// the second is to locate the inserted space right one char ahead.
+    ///
+    /// Only for non-css files like `index.hbs` : use the stylesheet model for css.
     fn insert(&mut self, insert: &str, find1: &str, find2: &str) -> Result<()> {
         let text = self.get();
-        let mut pos = text.find(find1).ok_or(Error::StrNotFound)?;
-        pos = pos + text[pos..].find(find2).ok_or(Error::StrNotFound)? - 1;
+        let not_found = || Error::StrNotFound(format!("`{find2}` after `{find1}`"));
+        let mut pos = text.find(find1).ok_or_else(not_found)?;
+        pos = pos + text[pos..].find(find2).ok_or_else(not_found)? - 1;
         self.get_mut().replace_range(pos..pos + 1, insert);
         Ok(())
     }
 
     /// content processing in `variables.css`
-    fn variables(&mut self, item: &str, value: &str) {
-        if item == "mobile-content-max-width" {
-            let media_on_screen = "@media only screen and (max-width:1439px)";
-            if self.get().contains(media_on_screen) {
-                return;
-            }
-            let content = format!(
-                "\n{media_on_screen} {{
- :root{{
-    --content-max-width: {};
-  }}
-}}\n\n",
-                value
-            );
-            self.insert(&content, "}", "/* Themes */").unwrap();
+    fn variables(&mut self, item: &str, value: &str) -> Result<()> {
+        if item == "code-font-family" {
+            self.set(":root", "--mono-font", value)
         } else if item.starts_with("light")
             | item.starts_with("ayu")
             | item.starts_with("rust")
             | item.starts_with("navy")
             | item.starts_with("coal")
         {
-            self.fore_arg(item, value);
-        } else if self.replace(item, value).is_err() {
-            self.insert(&format!("\n    --{}: {};\n", item, value), ":root", "}\n")
-                .unwrap();
+            self.fore_arg(item, value, "--")
+        } else {
+            self.set(":root", &format!("--{item}"), value)
         }
     }
 
-    /// deal with the config named `fore-arg: value;`
-    fn fore_arg(&mut self, item: &str, value: &str) {
+    /// Set `--content-max-width` below the pagetoc breakpoint, i.e. in the media query `below` .
+    /// The query written for an earlier breakpoint is renamed, so there's only one of them.
+    fn mobile_content_max_width(&mut self, below: &str, value: &str) -> Result<()> {
+        let (selector, property) = (":root", "--content-max-width");
+        let generated = |prelude: &str| {
+            prelude.starts_with("@media only screen and (max-width:")
+                || prelude.starts_with("@media not all and (min-width:")
+        };
+        let sheet = self.stylesheet();
+        let stale = sheet.rules.iter().find_map(|r| match r {
+            css::Rule::At(at)
+                if generated(&at.prelude)
+                    && at.prelude != css::normalize(below)
+                    && at.rule(selector, Some(property)).is_some() =>
+            {
+                // up to the `{` of the block
+                at.block.as_ref().map(|b| at.span.start..b.start - 1)
+            }
+            _ => None,
+        });
+        if let Some(prelude) = stale {
+            self.get_mut().replace_range(prelude, &format!("{below} "));
+        }
+        self.set_in_at_rule(below, Some(selector), selector, property, value)
+    }
+
+    /// Deal with the config named `fore-arg: value;` .
+    /// `prefix` is prepended to `arg` to get the property, e.g. `--` for css variables.
+    ///
+    /// A rule declaring the property is preferred;
+    /// otherwise the property is added to the rule of the longest `fore` .
+    fn fore_arg(&mut self, item: &str, value: &str, prefix: &str) -> Result<()> {
+        let sheet = self.stylesheet();
+        let mut candidates = Vec::new();
         for n in 2..item.split('-').count() + 1 {
-            for d in [true, false] {
+            for d in [".", "", ":"] {
                 for j in [" ", "-"] {
                     let (fore, arg) = Content::fore_check(item, n, d, j);
-                    if self.fore_replace(&fore, arg, value).is_ok() {
-                        return;
-                    }
+                    candidates.push((fore, format!("{prefix}{arg}")));
                 }
             }
         }
+        let found = candidates
+            .iter()
+            .find(|(fore, arg)| {
+                sheet
+                    .rule(fore, Some(arg))
+                    .is_some_and(|r| r.declaration(arg).is_some())
+            })
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .rev()
+                    .find(|(fore, _)| sheet.rule(fore, None).is_some())
+            });
+        let not_found = || Error::StrNotFound(format!("a selector matching `{item}`"));
+        let (fore, arg) = found.ok_or_else(not_found)?;
+        let rule = sheet.rule(fore, Some(arg)).ok_or_else(not_found)?;
+        self.set_in_rule(rule, arg, value);
+        Ok(())
+    }
+
+    /// Set an existing variable of a theme class in `variables.css` .
+    fn color(&mut self, theme: &str, name: &str, value: &str) -> Result<()> {
+        let sheet = self.stylesheet();
+        let (selector, variable) = (format!(".{theme}"), format!("--{name}"));
+        let rule = sheet
+            .rule(&selector, Some(&variable))
+            .ok_or_else(|| Error::StrNotFound(format!("theme `{selector}`")))?;
+        let old = match rule.declaration(&variable) {
+            Some(d) => &self.0[d.value.clone()],
+            None => {
+                let known = rule.declarations.iter().map(|d| d.property.as_str());
+                return Err(Error::UnknownVariable {
+                    variable,
+                    selector,
+                    similar: config::similar(&format!("--{name}"), known),
+                });
+            }
+        };
+        // a colour is only replaced by a colour
+        let value = if color::is_color(old) {
+            color::normalize(value).map_err(|reason| Error::InvalidValue {
+                item: variable.clone(),
+                reason,
+            })?
+        } else {
+            value.to_string()
+        };
+        self.set_in_rule(rule, &variable, &value);
+        Ok(())
+    }
+
+    /// Set the variables derived from a palette that the theme class declares.
+    fn palette(&mut self, palette: &Palette<'_>) -> Result<()> {
+        let selector = format!(".{}", palette.theme);
+        for (name, value) in &palette.colors {
+            let sheet = self.stylesheet();
+            let variable = format!("--{name}");
+            let rule = sheet
+                .rule(&selector, Some(&variable))
+                .ok_or_else(|| Error::StrNotFound(format!("theme `{selector}`")))?;
+            if rule.declaration(&variable).is_some() {
+                self.set_in_rule(rule, &variable, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add (or regenerate) the theme class of a custom theme in `variables.css` .
+    fn custom_theme(&mut self, theme: &CustomTheme<'_>) -> Result<()> {
+        let sheet = self.stylesheet();
+        let base = format!(".{}", theme.base);
+        let rule = sheet
+            .rule(&base, None)
+            .ok_or_else(|| Error::StrNotFound(format!("base theme `{base}`")))?;
+        let mut new = Content(format!(
+            ".{} {{{}}}",
+            theme.name,
+            &self.0[rule.block.clone()]
+        ));
+        for (name, value) in &theme.colors {
+            new.color(theme.name, name, value.get())?;
+        }
+        let text = self.get_mut();
+        match sheet.rule(&format!(".{}", theme.name), None) {
+            Some(old) => text.replace_range(old.span.clone(), new.get()),
+            None => {
+                text.truncate(text.trim_end().len());
+                text.push_str(&format!("\n\n{}\n", new.get()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add (or relabel) a theme in the theme picker of `index.hbs` .
+    fn theme_entry(&mut self, name: &str, label: &str, layout: &Layout) -> Result<()> {
+        let text = self.get();
+        let not_found = || Error::StrNotFound(format!("`</ul>` after `{}`", layout.theme_list));
+        let list = text.find(layout.theme_list).ok_or_else(not_found)?;
+        let end = list + text[list..].find("</ul>").ok_or_else(not_found)?;
+        let label = label
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let id = format!("{}{name}", layout.theme_id);
+        let button = format!(r#"<button role="menuitem" class="theme" id="{id}">"#);
+        if let Some(pos) = text[list..end]
+            .find(&button)
+            .map(|p| list + p + button.len())
+        {
+            let close = text[pos..].find("</button>").ok_or_else(not_found)?;
+            self.0.replace_range(pos..pos + close, &label);
+            return Ok(());
+        }
+        // one more level of indentation than `</ul>`
+        let line = text[..end].rfind('\n').map_or(0, |p| p + 1);
+        let indent = &text[line..end];
+        let entry = format!("{indent}    <li role=\"none\">{button}{label}</button></li>\n");
+        self.0.insert_str(line, &entry);
+        Ok(())
     }
 
     /// parse `fore-arg`:
@@ -278,21 +595,31 @@ impl Content {
     /// 1. one word begins with/without `.` , or even `:` : `.content` | `body` | `:root`
     /// 2. one word will very likely join more words with ` ` or `-`:
     ///    `.content main` | `.nav-chapters`
-    fn fore_check<'a>(item: &'a str, n: usize, dot: bool, joint: &'a str) -> (String, &'a str) {
+    fn fore_check<'a>(item: &'a str, n: usize, prefix: &str, joint: &'a str) -> (String, &'a str) {
         let v: Vec<&str> = item.splitn(n, '-').collect();
-        let d = if dot { "." } else { "" };
-        let fore = format!("\n{}{} {{", d, v[..n - 1].join(joint));
+        let fore = format!("{}{}", prefix, v[..n - 1].join(joint));
         (fore, v[n - 1])
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Theme<'a> {
-    pub cssfile: CssFile,
+    pub cssfile: CssFile<'a>,
     pub content: Content, // ultimate str to be processed
     content_cmp: Content,
     pub ready: Ready<'a>,
+    pub declares: Declares<'a>,
+    pub colors: Vec<Color<'a>>,
+    pub custom_themes: Vec<CustomTheme<'a>>,
+    pub palette: Option<Palette<'a>>,
+    pub pagetoc_options: Pagetoc,
+    /// anchors in mdBook's theme files
+    pub layout: &'static Layout,
+    /// the items in `ready` are set by the user and `strict = true` : a selector missing for one
+    /// of them fails the build, instead of being warned and skipped
+    pub strict: bool,
     pub dir: PathBuf,
+    output: Rc<RefCell<Output>>,
     path: PathBuf,
 }
 
@@ -302,7 +629,15 @@ impl Default for Theme<'_> {
             cssfile: CssFile::Custom(""),
             content: Content::default(),
             ready: Ready::default(),
+            declares: Declares::default(),
+            colors: Vec::new(),
+            custom_themes: Vec::new(),
+            palette: None,
+            pagetoc_options: Pagetoc::default(),
+            layout: Layout::bundled(),
+            strict: false,
             dir: PathBuf::new(),
+            output: Rc::default(),
             content_cmp: Content::default(),
             path: PathBuf::new(),
         }
@@ -311,21 +646,72 @@ impl Default for Theme<'_> {
 
 impl<'a> Theme<'a> {
     #[rustfmt::skip]
-    pub fn from(cssfile: CssFile, ready: Ready<'a>, dir: PathBuf) -> Self {
+    pub fn from(cssfile: CssFile<'a>, ready: Ready<'a>, dir: PathBuf) -> Self {
         Self { cssfile, ready, content: Content::default(), path: PathBuf::new(),
-        dir, content_cmp: Content::default() }
+        dir, content_cmp: Content::default(), declares: Declares::default(),
+        colors: Vec::new(), custom_themes: Vec::new(), palette: None,
+        pagetoc_options: Pagetoc::default(), layout: Layout::bundled(), strict: false,
+        output: Rc::default() }
+    }
+
+    /// Share where the contents go with other `Theme`s.
+    pub fn output(mut self, output: Rc<RefCell<Output>>) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Declarations to be set after the items in `ready` are processed.
+    pub fn declares(mut self, declares: Declares<'a>) -> Self {
+        self.declares = declares;
+        self
+    }
+
+    /// Colour variables to be set in `variables.css` .
+    pub fn colors(mut self, colors: Vec<Color<'a>>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Options to render `pagetoc.js` and `pagetoc.css` with.
+    pub fn pagetoc_options(mut self, options: Pagetoc) -> Self {
+        self.pagetoc_options = options;
+        self
+    }
+
+    /// The layout of mdBook's theme files, by the version of mdBook.
+    pub fn layout(mut self, layout: &'static Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Fail on a selector missing for the items in `ready` , which are set by the user.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// New themes to be added in `variables.css` and `index.hbs` .
+    pub fn custom_themes(mut self, custom_themes: Vec<CustomTheme<'a>>) -> Self {
+        self.custom_themes = custom_themes;
+        self
+    }
+
+    /// Colours derived from `[preprocessor.theme.palette]` to be set in `variables.css` .
+    pub fn palette(mut self, palette: Option<Palette<'a>>) -> Self {
+        self.palette = palette;
+        self
     }
 
     /// canonical procedure
-    pub fn process(self) -> Self {
-        self.cssfile().content().write_theme_file()
+    pub fn process(self) -> Result<Self> {
+        self.cssfile().content()?.write_theme_file()
     }
 
     /// Give a default or custom virtual css file marked to help content processing.
     fn cssfile(mut self) -> Self {
         let filename = self.cssfile.filename();
         self.path = self.dir.join(filename);
-        if self.path.exists() {
+        if RefCell::borrow(&self.output).exists(&self.path) {
             self.cssfile = CssFile::Custom(filename);
         }
         self
@@ -333,104 +719,339 @@ impl<'a> Theme<'a> {
 
     /// The **ultimate** content to be written into `theme` dir.
     /// An empty content means not having processed the content.
-    fn content(mut self) -> Self {
-        self.content = Content::from(self.cssfile, &self.dir);
+    fn content(mut self) -> Result<Self> {
+        if !RefCell::borrow(&self.output).exists(&self.path) {
+            self.layout
+                .check_missing(self.cssfile.filename(), &self.path)?;
+        }
+        self.content = Content::from(self.cssfile, &self.dir, &RefCell::borrow(&self.output))?;
         self.content_cmp = self.content.clone();
-        self.content_process(None);
-        self
+        self.content_process(None)?;
+        self.process_declares()?;
+        Ok(self)
     }
 
     /// process contents of different files
     #[rustfmt::skip]
-    fn content_process(&mut self, filename: Option<&str>) {
+    fn content_process(&mut self, filename: Option<&str>) -> Result<()> {
         match filename.map_or_else(|| self.cssfile, CssFile::variant) {
-            CssFile::Custom(f) => self.content_process(Some(f)),
+            CssFile::Custom(f) if filename.is_none() => self.content_process(Some(f)),
             CssFile::Variables => self.process_variables(),
             CssFile::General   => self.process_general(),
             CssFile::Chrome    => self.process_chrome(),
             CssFile::Index     => self.process_index(),
-            _ => (), // skip content processing
+            CssFile::PagetocJs | CssFile::PagetocCss => self.process_pagetoc(),
+            _ => Ok(()), // skip content processing
         }
     }
 
     /// Swich to another cssfile and process its content, which can repeat.
-    fn ready(mut self, cssfile: CssFile) -> Self {
+    fn ready(mut self, cssfile: CssFile<'a>) -> Result<Self> {
         self.cssfile = cssfile;
         self.ready = Ready::get_defualt(cssfile);
         self.process()
     }
 
     /// When `pagetoc = true` , a bunch of files need to change; if NOT true, don't call this.
-    fn pagetoc(self) {
-        self.ready(CssFile::Variables)
-            .ready(CssFile::Index)
-            .ready(CssFile::PagetocJs)
-            .ready(CssFile::PagetocCss)
-            .ready(CssFile::General)
-            .ready(CssFile::Chrome);
+    fn pagetoc(self) -> Result<()> {
+        self.ready(CssFile::Variables)?
+            .ready(CssFile::Index)?
+            .ready(CssFile::PagetocJs)?
+            .ready(CssFile::PagetocCss)?
+            .ready(CssFile::General)?
+            .ready(CssFile::Chrome)?;
+        Ok(())
     }
 
     /// create a css file on demand
-    fn write_theme_file(self) -> Self {
+    fn write_theme_file(self) -> Result<Self> {
         if self.content != self.content_cmp
             || ((self.cssfile == CssFile::PagetocJs || self.cssfile == CssFile::PagetocCss)
-                && !self.path.exists())
+                && !RefCell::borrow(&self.output).exists(&self.path))
         {
-            std::fs::write(&self.path, self.content.get().as_bytes()).unwrap();
+            let mut output = self.output.borrow_mut();
+            if !output.override_css(&self.path, self.content_cmp.get(), self.content.get()) {
+                output.write(&self.path, self.content.get())?;
+            }
         }
-        self
-    }
-
-    /// create the dirs on demand
-    pub(self) fn create_theme_dirs(dir: PathBuf) -> Result<()> {
-        std::fs::create_dir_all(dir.join("css")).map_err(|_| Error::DirNotCreated)?;
-        Ok(())
+        Ok(self)
     }
 }
 
 /// content processing
 impl Theme<'_> {
-    /// update content in `variables.css`
-    fn process_variables(&mut self) {
+    /// update content in `variables.css` : custom themes are added before palettes and colours,
+    /// so that those can be applied to custom themes too
+    fn process_variables(&mut self) -> Result<()> {
+        let below = self.pagetoc_options.below();
         for (item, value) in self.ready.item_value() {
-            self.content.variables(item.get(), value.get());
+            let result = match item.get() {
+                "mobile-content-max-width" => {
+                    self.content.mobile_content_max_width(&below, value.get())
+                }
+                item => self.content.variables(item, value.get()),
+            };
+            self.item_result(item.get(), result)?;
+        }
+        for t in &self.custom_themes {
+            self.content
+                .custom_theme(t)
+                .map_err(|e| e.config(&format!("custom-themes.{}", t.name), &self.path))?;
         }
+        if let Some(p) = &self.palette {
+            self.content
+                .palette(p)
+                .map_err(|e| e.config("palette", &self.path))?;
+        }
+        for c in &self.colors {
+            self.content
+                .color(c.theme, c.name, c.value.get())
+                .map_err(|e| e.config(&format!("colors.{}.{}", c.theme, c.name), &self.path))?;
+        }
+        Ok(())
     }
 
-    /// update content in `index.hbs`, if and only if `pagetoc = true` for now
-    fn process_index(&mut self) {
+    /// update content in `index.hbs` : the pagetoc when `pagetoc = true` , and custom themes.
+    /// The markup of the pagetoc follows the options, e.g. a static pagetoc is in the chapters
+    /// instead, so the empty one is taken out.
+    fn process_index(&mut self) -> Result<()> {
         let comment = "<!-- Page table of contents -->";
-        if self.content.get().contains(comment) {
-            return;
+        let pagetoc = self
+            .ready
+            .item_value()
+            .iter()
+            .any(|(i, _)| i.get() == "pagetoc");
+        let markup = self.pagetoc_options.index_markup();
+        let layout = self.layout;
+        if pagetoc {
+            layout.check(
+                self.content.get(),
+                &[layout.main, layout.content],
+                &self.path,
+            )?;
         }
-        let insert = format!(
-            r#" {comment}
-                        <div class="sidetoc"><nav class="pagetoc"></nav></div>
+        if !self.custom_themes.is_empty() {
+            layout.check(self.content.get(), &[layout.theme_list], &self.path)?;
+        }
+        let text = self.content.get_mut();
+        match text.find(comment) {
+            Some(c) if pagetoc => {
+                // the markup written before is in the lines right after the comment
+                let line = text[..c].rfind('\n').map_or(0, |i| i + 1);
+                let indent = text[line..c].to_string();
+                let (start, mut end) = (c + comment.len(), c + comment.len());
+                while let Some(l) = text[end..]
+                    .strip_prefix('\n')
+                    .and_then(|t| t.split('\n').next())
+                {
+                    if !pagetoc::is_index_markup(l.trim()) {
+                        break;
+                    }
+                    end += 1 + l.len();
+                }
+                // an `index.hbs` with its own pagetoc markup is left as it is
+                if end > start || !text.contains(r#"class="pagetoc""#) {
+                    let lines: String = markup.iter().map(|l| format!("\n{indent}{l}")).collect();
+                    text.replace_range(start..end, &lines);
+                }
+            }
+            None if pagetoc => {
+                let indent = " ".repeat(24);
+                let lines: String = markup.iter().map(|l| format!("\n{indent}{l}")).collect();
+                let insert = format!(" {comment}{lines}\n\n{indent}");
+                self.content
+                    .insert(&insert, layout.main, layout.content)
+                    .map_err(|e| e.config("pagetoc", &self.path))?;
+            }
+            _ => (),
+        }
+        for t in &self.custom_themes {
+            self.content
+                .theme_entry(t.name, &t.label, layout)
+                .map_err(|e| e.config(&format!("custom-themes.{}", t.name), &self.path))?;
+        }
+        Ok(())
+    }
 
-                        "#
-        );
-        self.content
-            .insert(&insert, "<main>", "{{{ content }}}")
-            .unwrap();
+    /// render `pagetoc.js` or `pagetoc.css` , unless the one in the theme dir is the user's own
+    fn process_pagetoc(&mut self) -> Result<()> {
+        let options = &self.pagetoc_options;
+        if RefCell::borrow(&self.output).exists(&self.path)
+            && !pagetoc::is_generated(self.content.get())
+        {
+            if *options != Pagetoc::default() {
+                eprintln!(
+                    "Warning: `{}` is not generated by mdbook-theme, so [preprocessor.theme.pagetoc] \
+                     doesn't apply to it; remove it to have it generated",
+                    self.path.display()
+                );
+            }
+            return Ok(());
+        }
+        *self.content.get_mut() = match self.cssfile.filename() {
+            "pagetoc.js" => options.js(),
+            _ => options.css(),
+        };
+        Ok(())
     }
 
-    /// update content in `css/general.css`
-    fn process_general(&mut self) {
+    /// update content in `css/general.css` : the font family is set on `html` as mdBook does
+    fn process_general(&mut self) -> Result<()> {
         for (item, value) in self.ready.item_value() {
-            let mut item_ = item.get();
-            if item_ == "root-font-size" {
-                // This case is annoying:
-                // field starts with `:` and value mixes with a comment
-                item_ = ":root-    font-size";
-            }
-            self.content.fore_arg(item_, value.get());
+            let result = match item.get() {
+                "body-font-family" => self.content.set("html", "font-family", value.get()),
+                item => self.content.fore_arg(item, value.get(), ""),
+            };
+            self.item_result(item.get(), result)?;
         }
+        Ok(())
     }
 
     /// update content in `css/chrome.css`
-    fn process_chrome(&mut self) {
+    fn process_chrome(&mut self) -> Result<()> {
         for (item, value) in self.ready.item_value() {
-            self.content.fore_arg(item.get(), value.get());
+            let result = self.content.fore_arg(item.get(), value.get(), "");
+            self.item_result(item.get(), result)?;
+        }
+        Ok(())
+    }
+
+    /// A customized theme file may lack the selector of an item: it's skipped with a warning,
+    /// unless the item is set by the user in strict mode.
+    fn item_result(&self, item: &str, result: Result<()>) -> Result<()> {
+        match result {
+            Err(Error::StrNotFound(anchor)) if !self.strict => {
+                eprintln!(
+                    "Warning: `{item}` is skipped: {anchor} is not found in `{}`",
+                    self.path.display()
+                );
+                Ok(())
+            }
+            result => result.map_err(|e| e.config(item, &self.path)),
         }
     }
+
+    /// update content in any css file with `[preprocessor.theme.css."file"]`
+    fn process_declares(&mut self) -> Result<()> {
+        if !self.cssfile.is_css() {
+            return Ok(());
+        }
+        for d in self.declares.get() {
+            let value = d.value.get();
+            match d.at_rule {
+                Some(at) => self
+                    .content
+                    .set_in_at_rule(at, None, d.selector, d.property, value),
+                None => self.content.set_rule(d.selector, d.property, value),
+            }
+            .map_err(|e| {
+                e.config(
+                    &format!("{} {{ {}: {value} }}", d.selector, d.property),
+                    &self.path,
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Content;
+
+    fn content(text: &str) -> Content {
+        Content(text.to_string())
+    }
+
+    #[test]
+    fn set_replaces_only_the_value() {
+        let text = "/* head */\n.a ,.b{\n  color : red  !important ; /* keep */\n\tmargin:0}\n";
+        let mut c = content(text);
+        c.set(".b", "color", "blue").unwrap();
+        assert_eq!(
+            c.get(),
+            "/* head */\n.a ,.b{\n  color : blue  !important ; /* keep */\n\tmargin:0}\n"
+        );
+        c.set(".a", "margin", "1px 2px").unwrap();
+        assert_eq!(
+            c.get(),
+            "/* head */\n.a ,.b{\n  color : blue  !important ; /* keep */\n\tmargin:1px 2px}\n"
+        );
+    }
+
+    #[test]
+    fn set_appends_a_missing_property() {
+        let mut c = content(".a { color: red } /* after */");
+        c.set(".a", "margin", "0").unwrap();
+        assert_eq!(c.get(), ".a { color: red;\n    margin: 0; } /* after */");
+
+        let mut c = content(".a {\n    color: red;\n}\n");
+        c.set(".a", "margin", "0").unwrap();
+        assert_eq!(c.get(), ".a {\n    color: red;\n    margin: 0;\n}\n");
+
+        let mut c = content(".a {}");
+        c.set(".a", "margin", "0").unwrap();
+        assert_eq!(c.get(), ".a {\n    margin: 0;\n}");
+    }
+
+    #[test]
+    fn set_in_at_rule_creates_the_at_rule() {
+        let media = "@media (max-width: 600px)";
+        let mut c = content("");
+        c.set_in_at_rule(media, None, ".a", "color", "red").unwrap();
+        assert_eq!(
+            c.get(),
+            "@media (max-width: 600px) {\n    .a {\n        color: red;\n    }\n}\n"
+        );
+
+        let mut c = content(".a { color: red }\n.b { color: red }\n");
+        c.set_in_at_rule(media, Some(".a"), ".b", "margin", "0")
+            .unwrap();
+        assert_eq!(
+            c.get(),
+            ".a { color: red }\n\n@media (max-width: 600px) {\n    .b {\n        margin: 0;\n    \
+             }\n}\n.b { color: red }\n"
+        );
+        assert!(c
+            .set_in_at_rule(media, Some(".c"), ".b", "margin", "0")
+            .is_ok());
+        assert!(content("")
+            .set_in_at_rule(media, Some(".c"), ".b", "margin", "0")
+            .is_err());
+    }
+
+    #[test]
+    fn set_in_at_rule_reuses_the_at_rule() {
+        let text = ".a { color: red }\n\n@media print {\n    .a { color: red }\n}\n";
+        let mut c = content(text);
+        c.set_in_at_rule("@media print", None, ".a", "color", "blue")
+            .unwrap();
+        assert_eq!(
+            c.get(),
+            ".a { color: red }\n\n@media print {\n    .a { color: blue }\n}\n"
+        );
+        c.set_in_at_rule("@media print", None, ".b", "margin", "0")
+            .unwrap();
+        assert_eq!(
+            c.get(),
+            ".a { color: red }\n\n@media print {\n    .a { color: blue }\n    .b {\n        \
+             margin: 0;\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn fore_arg_replaces_only_the_value() {
+        let text = ".light, html:not(.js) {\n    --bg: #fff; /* bg */\n    --links: #20609f;\n}\n\
+                    .rust {\n    --links: #2b79a2;\n}\n.content main {\n    margin: 0 auto;\n}\n";
+        let mut c = content(text);
+        c.fore_arg("rust-links", "red", "--").unwrap();
+        c.fore_arg("light-links", "#000", "--").unwrap();
+        c.fore_arg("content-main-margin", "0 1em", "").unwrap();
+        let expected = text
+            .replace("#2b79a2", "red")
+            .replace("#20609f", "#000")
+            .replace("0 auto", "0 1em");
+        assert_eq!(c.get(), expected);
+        assert!(c.fore_arg("coal-links", "red", "--").is_err());
+    }
 }
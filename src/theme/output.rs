@@ -0,0 +1,393 @@
+//! Where processed contents go: kept in memory until every file is processed and checked, then
+//! written into the theme dir, or shown as unified diffs against the files on disk in dry-run
+//! mode. So a config failing halfway leaves the theme dir as it was.
+//!
+//! In override mode, mdBook's stylesheets are kept in memory as well, and only what changes in
+//! them is written into the override stylesheet.
+
+use super::{
+    default::MDBOOK_CSS,
+    manifest::Manifest,
+    overrides::{changes, OVERRIDE_CSS},
+};
+use crate::{Error, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// lines of context around changes in a hunk
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Default)]
+pub struct Output {
+    pub dry_run: bool,
+    /// the theme dir
+    dir: PathBuf,
+    /// path -> (content on disk if any, latest content)
+    staged: BTreeMap<PathBuf, (Option<String>, String)>,
+    /// binary files to be written -> (whether each one exists, its content)
+    staged_bytes: BTreeMap<PathBuf, (bool, Vec<u8>)>,
+    /// records the written files: only used when not in dry-run mode
+    manifest: Option<Manifest>,
+    /// path -> (base content, latest content) of mdBook's stylesheets: only used in override mode
+    overrides: Option<BTreeMap<PathBuf, (String, String)>>,
+}
+
+impl Output {
+    pub fn new(theme_dir: &Path, dry_run: bool) -> Result<Self> {
+        let manifest = if dry_run {
+            None
+        } else {
+            Some(Manifest::load(theme_dir)?)
+        };
+        Ok(Output {
+            dry_run,
+            dir: theme_dir.to_path_buf(),
+            staged: BTreeMap::new(),
+            staged_bytes: BTreeMap::new(),
+            manifest,
+            overrides: None,
+        })
+    }
+
+    /// Write changes to mdBook's stylesheets into the override stylesheet instead.
+    pub fn override_mode(mut self, on: bool) -> Self {
+        self.overrides = if on { Some(BTreeMap::new()) } else { None };
+        self
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.staged.contains_key(path)
+            || self.staged_bytes.contains_key(path)
+            || self
+                .overrides
+                .as_ref()
+                .is_some_and(|o| o.contains_key(path))
+            || path.exists()
+    }
+
+    /// the latest content of a file
+    pub fn read(&self, path: &Path) -> Result<String> {
+        if let Some((_, content)) = self.overrides.as_ref().and_then(|o| o.get(path)) {
+            return Ok(content.clone());
+        }
+        match self.staged.get(path) {
+            Some((_, content)) => Ok(content.clone()),
+            None => {
+                std::fs::read_to_string(path).map_err(|e| Error::FileNotRead(path.to_path_buf(), e))
+            }
+        }
+    }
+
+    /// Stage `content` for `path` , which is written by `commit` .
+    pub fn write(&mut self, path: &Path, content: &str) -> Result<()> {
+        let original = match self.staged.remove(path) {
+            Some((original, _)) => original,
+            None => std::fs::read_to_string(path).ok(),
+        };
+        self.staged
+            .insert(path.to_path_buf(), (original, content.to_string()));
+        Ok(())
+    }
+
+    /// Stage a binary file like a font, unless it's already there.
+    pub fn write_bytes(&mut self, path: &Path, content: &[u8]) -> Result<()> {
+        if !std::fs::read(path).is_ok_and(|old| old == content) {
+            let staged = (path.exists(), content.to_vec());
+            self.staged_bytes.insert(path.to_path_buf(), staged);
+        }
+        Ok(())
+    }
+
+    /// Write the staged files into the theme dir, unless in dry-run mode.
+    pub fn commit(&mut self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        let staged = std::mem::take(&mut self.staged);
+        for (path, (original, content)) in &staged {
+            if original.as_deref() != Some(content.as_str()) {
+                self.write_file(path, content.as_bytes())?;
+            }
+        }
+        for (path, (_, content)) in std::mem::take(&mut self.staged_bytes) {
+            self.write_file(&path, &content)?;
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, content: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Error::DirNotCreated(parent.to_path_buf(), e.into()))?;
+        }
+        if let Some(manifest) = &mut self.manifest {
+            manifest.record(path, content)?;
+        }
+        std::fs::write(path, content).map_err(|e| Error::FileNotWritten(path.to_path_buf(), e))
+    }
+
+    /// Keep `content` of mdBook's stylesheet `path` in memory in override mode, and tell whether
+    /// it's kept. `base` is the content before the first change.
+    pub fn override_css(&mut self, path: &Path, base: &str, content: &str) -> bool {
+        let name = path.strip_prefix(&self.dir).unwrap_or(path);
+        let overrides = match &mut self.overrides {
+            Some(o) if MDBOOK_CSS.iter().any(|(f, _)| name == Path::new(f)) => o,
+            _ => return false,
+        };
+        let base = match overrides.remove(path) {
+            Some((base, _)) => base,
+            None => base.to_string(),
+        };
+        overrides.insert(path.to_path_buf(), (base, content.to_string()));
+        true
+    }
+
+    /// Write the override stylesheet in override mode.
+    pub fn write_overrides(&mut self) -> Result<()> {
+        let overrides = match &self.overrides {
+            Some(o) => o,
+            None => return Ok(()),
+        };
+        let mut css = String::from(
+            "/* Generated by mdbook-theme from [preprocessor.theme] in book.toml: \
+             changes here will be overwritten. */\n\n",
+        );
+        for (path, (base, content)) in overrides {
+            let changes = changes(base, content);
+            if !changes.is_empty() {
+                let name = path.strip_prefix(&self.dir).unwrap_or(path).display();
+                css.push_str(&format!("/* {name} */\n\n{changes}"));
+            }
+        }
+        let css = css.trim_end().to_string() + "\n";
+        let path = self.dir.join(OVERRIDE_CSS);
+        if self.exists(&path) && self.read(&path)? == css {
+            return Ok(());
+        }
+        self.write(&path, &css)
+    }
+
+    /// unified diffs of all staged files
+    pub fn diffs(&self) -> String {
+        // paths like `theme/css/variables.css`
+        let root = self.dir.parent().unwrap_or(&self.dir);
+        let mut diffs = String::new();
+        for (path, (original, content)) in &self.staged {
+            if original.as_deref() == Some(content.as_str()) {
+                continue;
+            }
+            let name = path.strip_prefix(root).unwrap_or(path).display();
+            let old_name = match original {
+                Some(_) => format!("a/{name}"),
+                None => String::from("/dev/null"),
+            };
+            let old = original.as_deref().unwrap_or("");
+            diffs.push_str(&unified_diff(old, content, &old_name, &format!("b/{name}")));
+        }
+        for (path, (exists, _)) in &self.staged_bytes {
+            let name = path.strip_prefix(root).unwrap_or(path).display();
+            let old_name = match exists {
+                true => format!("a/{name}"),
+                false => String::from("/dev/null"),
+            };
+            diffs.push_str(&format!("Binary files {old_name} and b/{name} differ\n"));
+        }
+        diffs
+    }
+}
+
+/// positions in the old and new lines
+#[derive(Clone, Copy)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize, usize),
+    Insert(usize, usize),
+}
+
+impl Op {
+    fn pos(self) -> (usize, usize) {
+        match self {
+            Op::Equal(i, j) | Op::Delete(i, j) | Op::Insert(i, j) => (i, j),
+        }
+    }
+}
+
+/// A line-based unified diff computed from the longest common subsequence.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let a: Vec<_> = old.lines().collect();
+    let b: Vec<_> = new.lines().collect();
+    let ops = ops(&a, &b);
+
+    let changed: Vec<_> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(k, _)| k)
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    let mut k = 0;
+    while k < changed.len() {
+        // merge changes whose contexts overlap into one hunk
+        let start = changed[k].saturating_sub(CONTEXT);
+        let mut end = changed[k];
+        while k < changed.len() && changed[k] <= end + 2 * CONTEXT {
+            end = changed[k];
+            k += 1;
+        }
+        let end = (end + CONTEXT + 1).min(ops.len());
+        out.push_str(&hunk(&ops[start..end], &a, &b));
+    }
+    out
+}
+
+/// The number of lines deleted or inserted from `old` to `new` .
+pub fn changed_lines(old: &str, new: &str) -> usize {
+    let a: Vec<_> = old.lines().collect();
+    let b: Vec<_> = new.lines().collect();
+    ops(&a, &b)
+        .iter()
+        .filter(|op| !matches!(op, Op::Equal(..)))
+        .count()
+}
+
+/// edit operations from `a` to `b`
+fn ops(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let (n, m) = (a.len(), b.len());
+
+    // lcs[i][j]: length of the lcs of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut ops) = (0, 0, Vec::new());
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            ops.push(Op::Equal(i, j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(i, j));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+fn hunk(ops: &[Op], a: &[&str], b: &[&str]) -> String {
+    let (old_start, new_start) = ops[0].pos();
+    let (mut old_len, mut new_len, mut body) = (0, 0, String::new());
+    for op in ops {
+        match *op {
+            Op::Equal(i, _) => {
+                old_len += 1;
+                new_len += 1;
+                body.push_str(&format!(" {}\n", a[i]));
+            }
+            Op::Delete(i, _) => {
+                old_len += 1;
+                body.push_str(&format!("-{}\n", a[i]));
+            }
+            Op::Insert(_, j) => {
+                new_len += 1;
+                body.push_str(&format!("+{}\n", b[j]));
+            }
+        }
+    }
+    // an empty range starts at the line before it
+    let range = |start: usize, len: usize| match len {
+        0 => format!("{start},0"),
+        _ => format!("{},{len}", start + 1),
+    };
+    format!(
+        "@@ -{} +{} @@\n{body}",
+        range(old_start, old_len),
+        range(new_start, new_len)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_lines, unified_diff, Output};
+    use crate::test_dir;
+
+    fn lines(range: std::ops::RangeInclusive<usize>) -> String {
+        range.map(|n| format!("{n}\n")).collect()
+    }
+
+    #[test]
+    fn hunk_headers_count_from_one() {
+        let old = lines(1..=10);
+        let new = old.replace("5\n", "five\n");
+        assert_eq!(
+            unified_diff(&old, &new, "a/f", "b/f"),
+            "--- a/f\n+++ b/f\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+        assert_eq!(unified_diff(&old, &old, "a/f", "b/f"), "");
+        assert_eq!(changed_lines(&old, &new), 2);
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let old = lines(1..=30);
+        // 6 lines apart: contexts meet, one hunk
+        let near = old.replace("10\n", "ten\n").replace("16\n", "sixteen\n");
+        let diff = unified_diff(&old, &near, "a/f", "b/f");
+        assert_eq!(diff.matches("@@ -").count(), 1, "{}", diff);
+        assert!(diff.contains("@@ -7,13 +7,13 @@"), "{}", diff);
+        // 7 lines apart: two hunks
+        let far = old.replace("10\n", "ten\n").replace("17\n", "seventeen\n");
+        let diff = unified_diff(&old, &far, "a/f", "b/f");
+        assert!(diff.contains("@@ -7,7 +7,7 @@"), "{}", diff);
+        assert!(diff.contains("@@ -14,7 +14,7 @@"), "{}", diff);
+    }
+
+    #[test]
+    fn created_and_deleted_files() {
+        assert_eq!(
+            unified_diff("", "a\nb\n", "/dev/null", "b/f"),
+            "--- /dev/null\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+        assert_eq!(
+            unified_diff("a\nb\n", "", "a/f", "/dev/null"),
+            "--- a/f\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-a\n-b\n"
+        );
+    }
+
+    #[test]
+    fn dry_run_diffs_created_files_from_dev_null() {
+        let root = test_dir("dry-run");
+        let dir = root.join("theme");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old.css"), "a\n").unwrap();
+        let mut output = Output::new(&dir, true).unwrap();
+        output.write(&dir.join("old.css"), "b\n").unwrap();
+        output.write(&dir.join("new.css"), "c\n").unwrap();
+        output.write(&dir.join("twice.css"), "d\n").unwrap();
+        output.write(&dir.join("twice.css"), "e\n").unwrap();
+        output.commit().unwrap();
+        let diffs = output.diffs();
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(files.len(), 1, "dry-run writes nothing");
+        assert_eq!(
+            diffs,
+            "--- /dev/null\n+++ b/theme/new.css\n@@ -0,0 +1,1 @@\n+c\n\
+             --- a/theme/old.css\n+++ b/theme/old.css\n@@ -1,1 +1,1 @@\n-a\n+b\n\
+             --- /dev/null\n+++ b/theme/twice.css\n@@ -0,0 +1,1 @@\n+e\n"
+        );
+    }
+}
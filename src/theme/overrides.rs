@@ -0,0 +1,157 @@
+//! `override = true` : instead of copying mdBook's stylesheets into the theme dir, only the
+//! declarations this tool changes are written into one stylesheet, which is loaded after
+//! mdBook's ones via `additional-css` . mdBook's files stay untouched, so they follow
+//! mdBook upgrades.
+//!
+//! The stylesheets are still edited as usual, but in memory; the override stylesheet is the
+//! difference between each edited stylesheet and the one it comes from.
+
+use super::css::{Declaration, Rule, Stylesheet};
+use std::collections::HashMap;
+
+/// the override stylesheet in the theme dir
+pub const OVERRIDE_CSS: &str = "mdbook-theme.css";
+
+/// Rules in `content` with declarations that are new or changed compared with `base` .
+/// Only those declarations are kept in each rule.
+pub fn changes(base: &str, content: &str) -> String {
+    let (old, new) = (Stylesheet::parse(base), Stylesheet::parse(content));
+    let mut out = String::new();
+    rules(base, &old.rules, content, &new.rules, "", &mut out);
+    out
+}
+
+fn rules(base: &str, old: &[Rule], content: &str, new: &[Rule], indent: &str, out: &mut String) {
+    // the n-th rule with the same selectors (or prelude) corresponds to the n-th one in `old`
+    let mut seen = HashMap::new();
+    for rule in new {
+        let k = key(rule);
+        let nth = seen.entry(k.clone()).or_insert(0);
+        let counterpart = old.iter().filter(|r| key(r) == k).nth(*nth);
+        *nth += 1;
+        match (rule, counterpart) {
+            (Rule::Style(s), counterpart) => {
+                let changed: Vec<_> = (s.declarations.iter().enumerate())
+                    .filter(|&(i, d)| match counterpart {
+                        Some(Rule::Style(o)) => counterpart_of(&s.declarations, i, &o.declarations)
+                            .is_none_or(|o| declaration(base, o) != declaration(content, d)),
+                        _ => true,
+                    })
+                    .map(|(_, d)| d)
+                    .collect();
+                if !changed.is_empty() {
+                    out.push_str(&format!("{indent}{} {{\n", s.selectors.join(", ")));
+                    for d in changed {
+                        out.push_str(&format!("{indent}    {};\n", declaration(content, d)));
+                    }
+                    out.push_str(&format!("{indent}}}\n\n"));
+                }
+            }
+            (Rule::At(a), Some(Rule::At(o))) if !a.rules.is_empty() => {
+                let mut inner = String::new();
+                rules(base, &o.rules, content, &a.rules, "    ", &mut inner);
+                if !inner.is_empty() {
+                    let inner = inner.trim_end();
+                    out.push_str(&format!("{} {{\n{inner}\n}}\n\n", a.prelude));
+                }
+            }
+            // `@font-face` , `@import` , ...
+            (Rule::At(a), Some(Rule::At(o))) if content[a.span.clone()] == base[o.span.clone()] => {
+            }
+            (Rule::At(a), _) => out.push_str(&format!("{}\n\n", &content[a.span.clone()])),
+        }
+    }
+}
+
+/// The declaration in `old` corresponding to `new[i]` : a property can be declared more than
+/// once in a rule, e.g. after a vendor prefixed value, so the n-th ones correspond.
+fn counterpart_of<'d>(
+    new: &[Declaration],
+    i: usize,
+    old: &'d [Declaration],
+) -> Option<&'d Declaration> {
+    let property = &new[i].property;
+    let n = new[..i].iter().filter(|d| &d.property == property).count();
+    old.iter().filter(|d| &d.property == property).nth(n)
+}
+
+/// selectors of a style rule, or the prelude of an at-rule
+fn key(rule: &Rule) -> String {
+    match rule {
+        Rule::Style(s) => s.selectors.join(", "),
+        Rule::At(a) => a.prelude.clone(),
+    }
+}
+
+/// `property: value` or `property: value !important`
+fn declaration(text: &str, d: &Declaration) -> String {
+    let value = &text[d.value.clone()];
+    if text[d.value.end..d.span.end].contains("!important") {
+        format!("{}: {value} !important", d.property)
+    } else {
+        format!("{}: {value}", d.property)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::changes;
+
+    const BASE: &str = "\
+:root { --a: 1px; --b: red; }
+.x, .y { color: red; margin: 0 !important; }
+@media (max-width: 600px) {
+    .x { color: blue; }
+    .z { margin: 0; }
+}
+@font-face { font-family: A; src: url(a.woff2); }
+";
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(changes(BASE, BASE), "");
+    }
+
+    #[test]
+    fn only_changed_declarations() {
+        let content = BASE
+            .replace("--b: red", "--b: green")
+            .replace("margin: 0 !important", "margin: 1px !important");
+        assert_eq!(
+            changes(BASE, &content),
+            ":root {\n    --b: green;\n}\n\n.x, .y {\n    margin: 1px !important;\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn changes_in_media() {
+        let content = BASE.replace("color: blue", "color: green");
+        assert_eq!(
+            changes(BASE, &content),
+            "@media (max-width: 600px) {\n    .x {\n        color: green;\n    }\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn new_rules_and_declarations() {
+        let content = BASE
+            .replace(".z { margin: 0; }", ".z { margin: 0; padding: 0; }")
+            .replace(
+                "@font-face",
+                ".new { color: red; }\n@media print { .x { color: black; } }\n@font-face",
+            );
+        assert_eq!(
+            changes(BASE, &content),
+            "@media (max-width: 600px) {\n    .z {\n        padding: 0;\n    }\n}\n\n\
+             .new {\n    color: red;\n}\n\n\
+             @media print { .x { color: black; } }\n\n"
+        );
+    }
+
+    #[test]
+    fn repeated_properties_pair_in_order() {
+        let base = ".x { display: -webkit-box; display: flex; }";
+        let content = ".x { display: -webkit-box; display: grid; }";
+        assert_eq!(changes(base, content), ".x {\n    display: grid;\n}\n\n");
+    }
+}
@@ -1,68 +1,281 @@
+// Generated by mdbook-theme from [preprocessor.theme.pagetoc] in book.toml: changes here will be overwritten.
 // src: https://github.com/JorelAli/mdBook-pagetoc
 
-// Un-active everything when you click it
-Array.prototype.forEach.call(document.getElementsByClassName("pagetoc")[0].children, function(el, i) {
-    el.addEventHandler("click", function() {
-        Array.prototype.forEach.call(document.getElementsByClassName("pagetoc")[0].children, function(el, i) {
-            el.classList.remove("active");
-        });
-        el.classList.add("active");
+// headings from `min-level` to `max-level` , found by `selector`
+var pagetocMinLevel = {{min-level}};
+var pagetocMaxLevel = {{max-level}};
+var pagetocSelector = {{selector}};
+// scroll smoothly to the heading of a clicked link, unless the reader prefers reduced motion
+var pagetocSmoothScroll = {{smooth-scroll}};
+// a tree of links, where the subsections of sections not being read are folded
+var pagetocCollapsible = {{collapsible}};
+// labels in the language of the book, see `[preprocessor.theme.strings]`
+var pagetocStrings = {{strings}};
+// the id of mdBook's menu bar, by the version of mdBook
+var pagetocMenuBar = {{menu-bar}};
+
+// `<!-- pagetoc: ... -->` in a chapter, or `exclude` , can turn the pagetoc off or change the levels
+var pagetocChapter = document.querySelector("main .pagetoc-chapter");
+var pagetocOff = pagetocChapter !== null && pagetocChapter.hasAttribute("data-off");
+if (pagetocChapter !== null && !pagetocOff) {
+    pagetocMinLevel = Number(pagetocChapter.getAttribute("data-min-level")) || pagetocMinLevel;
+    pagetocMaxLevel = Number(pagetocChapter.getAttribute("data-max-level")) || pagetocMaxLevel;
+}
+
+// The level of a heading, or of the heading containing the anchor; 0 if neither.
+var headingLevel = function(el) {
+    var tag = /^H([1-6])$/.exec(el.tagName) || /^H([1-6])$/.exec(el.parentElement.tagName);
+    return tag ? Number(tag[1]) : 0;
+};
+
+var headings = function() {
+    return Array.prototype.filter.call(document.querySelectorAll(pagetocSelector), function(el) {
+        var level = headingLevel(el);
+        return level >= pagetocMinLevel && level <= pagetocMaxLevel;
     });
-});
+};
+
+// anchors have their own links, and headings are linked by their ids
+var headingHref = function(el) {
+    var a = document.createElement("a");
+    a.href = el.href || "#" + el.id;
+    return a.href;
+};
+
+// the heading a pagetoc link points to
+var linkTarget = function(link) {
+    return document.getElementById(decodeURIComponent(link.hash.slice(1)));
+};
+
+// The height of the menu bar covering the top of the page, plus the margin mdBook leaves above
+// a heading jumped to (see `:target` in general.css).
+var topOffset = function() {
+    var menuBar = document.getElementById(pagetocMenuBar);
+    var em = parseFloat(getComputedStyle(document.documentElement).fontSize) || 16;
+    return (menuBar ? menuBar.offsetHeight : 0) + em / 2;
+};
+
+var pagetocLinks = [];
+// the links in "On this page" below the breakpoint, see `mobile`
+var mobileLinks = [];
+// "collapse all" folds every section until the reader moves to another one
+var pagetocCollapsedAll = false;
+var pagetocActive = null;
 
-var updateFunction = function() {
+// the `<ul>` of subsections in a `<li>` , created if asked to
+var childList = function(li, create) {
+    for (var i = 0; i < li.children.length; i++) {
+        if (li.children[i].tagName == "UL") {
+            return li.children[i];
+        }
+    }
+    return create ? li.appendChild(document.createElement("ul")) : null;
+};
 
-    var id;
-    var elements = document.getElementsByClassName("header");
-    Array.prototype.forEach.call(elements, function(el, i) {
-        if (window.pageYOffset >= el.offsetTop) {
-            id = el;
+// Nest the links in `<ul>` by their heading levels, as the static pagetoc does.
+var tree = function(items) {
+    var root = document.createElement("ul");
+    var open = [];
+    items.forEach(function(item) {
+        while (open.length > 0 && open[open.length - 1].level >= item.level) {
+            open.pop();
         }
+        var list = open.length > 0 ? childList(open[open.length - 1].li, true) : root;
+        var li = list.appendChild(document.createElement("li"));
+        li.appendChild(item.link);
+        open.push({ level: item.level, li: li });
     });
+    return root;
+};
 
-    Array.prototype.forEach.call(document.getElementsByClassName("pagetoc")[0].children, function(el, i) {
-        el.classList.remove("active");
+// A section is unfolded if the reader says so, or if it's being read.
+var fold = function(pagetoc) {
+    Array.prototype.forEach.call(pagetoc.getElementsByTagName("li"), function(li) {
+        if (!childList(li, false)) {
+            return;
+        }
+        var reading = li.getElementsByClassName("active").length > 0;
+        var open = li.pagetocOpen !== undefined ? li.pagetocOpen : reading && !pagetocCollapsedAll;
+        li.classList.toggle("pagetoc-folded", !open);
+        li.firstChild.setAttribute("aria-expanded", String(open));
     });
+};
 
-    Array.prototype.forEach.call(document.getElementsByClassName("pagetoc")[0].children, function(el, i) {
-        if (id.href.localeCompare(el.href) == 0) {
-            el.classList.add("active");
+// Add a toggle to each section with subsections, and "collapse all" on the top.
+var collapsible = function(pagetoc) {
+    Array.prototype.forEach.call(pagetoc.getElementsByTagName("li"), function(li) {
+        if (!childList(li, false)) {
+            return;
         }
+        var toggle = document.createElement("button");
+        toggle.type = "button";
+        toggle.className = "pagetoc-toggle";
+        toggle.setAttribute("aria-label", pagetocStrings["toggle-subsections"]);
+        var link = li.getElementsByTagName("a")[0];
+        toggle.style.setProperty("--pagetoc-depth", link.style.getPropertyValue("--pagetoc-depth"));
+        toggle.addEventListener("click", function() {
+            li.pagetocOpen = li.classList.contains("pagetoc-folded");
+            fold(pagetoc);
+        });
+        li.insertBefore(toggle, li.firstChild);
+    });
+    var all = document.createElement("button");
+    all.type = "button";
+    all.className = "pagetoc-collapse-all";
+    all.appendChild(document.createTextNode(pagetocStrings["collapse-all"]));
+    all.addEventListener("click", function() {
+        Array.prototype.forEach.call(pagetoc.getElementsByTagName("li"), function(li) {
+            li.pagetocOpen = undefined;
+        });
+        pagetocCollapsedAll = true;
+        fold(pagetoc);
     });
+    pagetoc.insertBefore(all, pagetoc.firstChild);
 };
 
-// Populate sidebar on load
-window.addEventListener('load', function() {
-    var pagetoc = document.getElementsByClassName("pagetoc")[0];
-    var elements = document.getElementsByClassName("header");
-    Array.prototype.forEach.call(elements, function(el, i) {
-        var link = document.createElement("a");
-
-        // Indent shows hierarchy
-        var indent = "";
-        switch (el.parentElement.tagName) {
-            case "H2":
-                indent = "20px";
-                break;
-            case "H3":
-                indent = "40px";
-                break;
-            case "H4":
-                indent = "60px";
-                break;
-            default:
-                break;
+// Scroll the pagetoc itself, not the page, to show the link.
+var keepVisible = function(pagetoc, link) {
+    if (pagetoc.scrollHeight <= pagetoc.clientHeight) {
+        return;
+    }
+    var top = link.getBoundingClientRect().top - pagetoc.getBoundingClientRect().top;
+    if (top < 0 || top + link.offsetHeight > pagetoc.clientHeight) {
+        pagetoc.scrollTop += top - (pagetoc.clientHeight - link.offsetHeight) / 2;
+    }
+};
+
+var setActive = function(pagetoc, active) {
+    pagetocLinks.forEach(function(link) {
+        link.classList.toggle("active", link === active);
+    });
+    mobileLinks.forEach(function(link) {
+        link.classList.toggle("active", active !== null && link.hash === active.hash);
+    });
+    if (pagetocCollapsible) {
+        if (active !== pagetocActive) {
+            pagetocCollapsedAll = false;
+        }
+        fold(pagetoc);
+    }
+    pagetocActive = active;
+    if (active) {
+        keepVisible(pagetoc, active);
+    }
+};
+
+// The active link is the one of the last heading scrolled past the menu bar.
+var updateFunction = function(pagetoc) {
+    var line = topOffset() + 1;
+    var active = null;
+    pagetocLinks.forEach(function(link) {
+        var target = linkTarget(link);
+        if (target && target.getBoundingClientRect().top <= line) {
+            active = link;
         }
+    });
+    setActive(pagetoc, active);
+};
 
-        link.appendChild(document.createTextNode(el.text));
-        link.style.paddingLeft = indent;
-        link.href = el.href;
-        pagetoc.appendChild(link);
+var smoothScroll = function(pagetoc, link, event) {
+    var target = linkTarget(link);
+    var reduced = window.matchMedia && window.matchMedia("(prefers-reduced-motion: reduce)").matches;
+    if (!pagetocSmoothScroll || reduced || !target) {
+        return;
+    }
+    event.preventDefault();
+    window.scrollTo({
+        top: target.getBoundingClientRect().top + window.pageYOffset - topOffset(),
+        behavior: "smooth",
     });
-    updateFunction.call();
-});
+    history.pushState(null, "", link.hash);
+    setActive(pagetoc, link);
+};
 
+// Populate sidebar on load, unless it's rendered at build time (`static = true`)
+window.addEventListener('load', function() {
+    var pagetoc = document.getElementsByClassName("pagetoc")[0];
+    var mobile = document.getElementsByClassName("pagetoc-mobile")[0];
+    if (!pagetoc) {
+        return;
+    }
+    if (pagetoc.children.length == 0) {
+        var elements = pagetocOff ? [] : headings();
+        if (elements.length == 0) {
+            // no empty pagetoc
+            pagetoc.parentElement.style.display = "none";
+            if (mobile) {
+                mobile.parentElement.style.display = "none";
+            }
+            return;
+        }
+        var items = Array.prototype.map.call(elements, function(el) {
+            var link = document.createElement("a");
 
+            // Indent shows hierarchy: see `--pagetoc-depth` in pagetoc.css
+            link.appendChild(document.createTextNode(el.textContent));
+            link.style.setProperty("--pagetoc-depth", headingLevel(el) - pagetocMinLevel);
+            link.href = headingHref(el);
+            return { level: headingLevel(el), link: link };
+        });
+        if (pagetocCollapsible) {
+            pagetoc.appendChild(tree(items));
+        } else {
+            items.forEach(function(item) {
+                pagetoc.appendChild(item.link);
+            });
+        }
+    }
+    if (mobile) {
+        if (mobile.children.length == 0) {
+            // the same links, before the toggles of `collapsible` are added
+            Array.prototype.forEach.call(pagetoc.children, function(el) {
+                mobile.appendChild(el.cloneNode(true));
+            });
+        }
+        mobileLinks = Array.prototype.slice.call(mobile.getElementsByTagName("a"));
+        mobileLinks.forEach(function(link) {
+            link.addEventListener("click", function() {
+                mobile.parentElement.open = false;
+            });
+        });
+    }
+    if (pagetocCollapsible) {
+        collapsible(pagetoc);
+    }
+    pagetocLinks = Array.prototype.slice.call(pagetoc.getElementsByTagName("a"));
+    pagetocLinks.forEach(function(link) {
+        link.addEventListener("click", function(event) {
+            setActive(pagetoc, link);
+            smoothScroll(pagetoc, link, event);
+        });
+    });
 
-// Handle active elements on scroll
-window.addEventListener("scroll", updateFunction);
+    // Handle active elements when headings cross the menu bar, instead of on every scroll
+    var update = function() {
+        updateFunction(pagetoc);
+    };
+    if ("IntersectionObserver" in window) {
+        var observer = new IntersectionObserver(update, {
+            rootMargin: "-" + Math.round(topOffset()) + "px 0px 0px 0px",
+            threshold: [0, 1],
+        });
+        pagetocLinks.forEach(function(link) {
+            var target = linkTarget(link);
+            if (target) {
+                observer.observe(target);
+            }
+        });
+    } else {
+        var ticking = false;
+        window.addEventListener("scroll", function() {
+            if (!ticking) {
+                ticking = true;
+                window.requestAnimationFrame(function() {
+                    ticking = false;
+                    update();
+                });
+            }
+        });
+    }
+    update();
+});
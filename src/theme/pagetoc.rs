@@ -0,0 +1,526 @@
+//! `[preprocessor.theme.pagetoc]` : options of the pagetoc, and `pagetoc.js` / `pagetoc.css`
+//! rendered from the templates with them.
+//!
+//! The rendered files carry a "Generated by mdbook-theme" line. A pagetoc file in the theme dir
+//! without it (and not written by an earlier version) is the user's own, and left untouched.
+
+use super::{
+    default::{PAGETOCCSS, PAGETOCJS},
+    layout::Layout,
+    manifest,
+    strings::Strings,
+};
+use crate::{Error, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use mdbook::{
+    book::{Book, BookItem},
+    utils::unique_id_from_content,
+};
+use pulldown_cmark::Event;
+use regex::Regex;
+use std::{collections::HashMap, sync::OnceLock};
+
+/// the line telling the file is rendered by this tool
+const GENERATED: &str = "Generated by mdbook-theme from [preprocessor.theme.pagetoc]";
+
+/// `<!-- pagetoc: ... -->` in a chapter, compiled once for all the chapters
+static MARKER: OnceLock<Regex> = OnceLock::new();
+/// headings in the html of a chapter, with the level, id, class and content
+static HEADING: OnceLock<Regex> = OnceLock::new();
+/// html tags in the content of a heading
+static TAG: OnceLock<Regex> = OnceLock::new();
+
+/// sha256 of `pagetoc.js` and `pagetoc.css` written verbatim by earlier versions
+const LEGACY: &[&str] = &[
+    "6e50a3ef6a560695a8e64343e65ceb411a41347dd9de0d599caadab2d94b5948",
+    "ef9afe1ff6553bf863b8267a949aac9cee123990f9c62db62d26856c8ec1e074",
+];
+
+/// keys in `[preprocessor.theme.pagetoc]`
+pub const KEYS: &[&str] = &[
+    "min-level",
+    "max-level",
+    "indent",
+    "selector",
+    "position",
+    "breakpoint",
+    "sticky-offset",
+    "static",
+    "exclude",
+    "smooth-scroll",
+    "collapsible",
+    "mobile",
+];
+
+/// Where the pagetoc is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Position {
+    /// on the right of the content, from the breakpoint on
+    Right,
+    /// on the left of the content, from the breakpoint on
+    Left,
+    /// at the top of the content, at any width
+    Inline,
+}
+
+impl Position {
+    pub fn from(s: &str) -> Option<Self> {
+        match s {
+            "right" => Some(Position::Right),
+            "left" => Some(Position::Left),
+            "inline" => Some(Position::Inline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pagetoc {
+    /// headings from `<h{min_level}>` to `<h{max_level}>` are listed
+    pub min_level: u8,
+    pub max_level: u8,
+    /// indentation per level below `min_level`
+    pub indent: String,
+    /// the headings, or the anchors in them (`<a class="header">` in mdBook)
+    pub selector: String,
+    pub position: Position,
+    /// the minimal viewport width to show the pagetoc on a side
+    pub breakpoint: String,
+    /// the distance between the top of the viewport and the sticky pagetoc
+    pub sticky_offset: String,
+    /// `static = true` : render the pagetoc into each chapter at build time, instead of by js
+    pub static_toc: bool,
+    /// chapters without the pagetoc, as paths or globs relative to `src`
+    pub exclude: Vec<String>,
+    /// scroll smoothly to the heading of a clicked link
+    pub smooth_scroll: bool,
+    /// a tree of links, where the subsections of sections not being read are folded
+    pub collapsible: bool,
+    /// an "On this page" block on the top of the content below the breakpoint
+    pub mobile: bool,
+    /// the labels in the language of the book
+    pub strings: Strings,
+    /// the theme layout of the running mdBook, for the id of the menu bar
+    pub layout: &'static Layout,
+}
+
+impl Default for Pagetoc {
+    fn default() -> Self {
+        Pagetoc {
+            min_level: 1,
+            max_level: 6,
+            indent: String::from("20px"),
+            selector: String::from(".header"),
+            position: Position::Right,
+            breakpoint: String::from("1440px"),
+            sticky_offset: String::from("var(--menu-bar-height)"),
+            static_toc: false,
+            exclude: Vec::new(),
+            smooth_scroll: false,
+            collapsible: false,
+            mobile: false,
+            strings: Strings::default(),
+            layout: Layout::bundled(),
+        }
+    }
+}
+
+impl Pagetoc {
+    pub fn js(&self) -> String {
+        // a js string literal
+        let selector = serde_json::Value::from(self.selector.as_str()).to_string();
+        let menu_bar = serde_json::Value::from(self.layout.menu_bar).to_string();
+        render(
+            template(PAGETOCJS),
+            &[
+                ("min-level", self.min_level.to_string()),
+                ("max-level", self.max_level.to_string()),
+                ("selector", selector),
+                ("smooth-scroll", self.smooth_scroll.to_string()),
+                ("collapsible", self.collapsible.to_string()),
+                ("strings", self.strings.js()),
+                ("menu-bar", menu_bar),
+            ],
+        )
+    }
+
+    pub fn css(&self) -> String {
+        let side = self.position != Position::Inline;
+        let edge = match self.position {
+            Position::Left => "right",
+            _ => "left",
+        };
+        let flags = [
+            ("side", side),
+            ("inline", !side),
+            ("collapsible", self.collapsible),
+            ("mobile", self.has_mobile()),
+        ];
+        let text = sections(template(PAGETOCCSS), &flags);
+        render(
+            text,
+            &[
+                ("indent", self.indent.clone()),
+                ("below", self.below()),
+                ("above", self.above()),
+                ("edge", edge.to_string()),
+                ("sticky-offset", self.sticky_offset.clone()),
+            ],
+        )
+    }
+
+    /// An inline pagetoc is already on the top at any width.
+    fn has_mobile(&self) -> bool {
+        self.mobile && self.position != Position::Inline
+    }
+
+    /// The pagetoc markup in `index.hbs` , filled by `pagetoc.js` ; a static one is in the
+    /// chapters instead.
+    pub fn index_markup(&self) -> Vec<String> {
+        let mut markup = Vec::new();
+        if !self.static_toc {
+            markup.push(self.sidetoc(""));
+            if self.has_mobile() {
+                markup.push(self.mobiletoc(""));
+            }
+        }
+        markup
+    }
+
+    /// The media query below the breakpoint, where the pagetoc is not on a side.
+    /// It's `@media only screen and (max-width:1439px)` for `1440px` , as it used to be.
+    pub fn below(&self) -> String {
+        match self.breakpoint.strip_suffix("px").map(str::parse::<f64>) {
+            Some(Ok(px)) => format!("@media only screen and (max-width:{}px)", px - 1.0),
+            _ => format!("@media not all and (min-width:{})", self.breakpoint),
+        }
+    }
+
+    /// Apply `exclude` and the `<!-- pagetoc: ... -->` markers to each chapter: the static pagetoc
+    /// is rendered into it, or the js one is told what to do by a hidden
+    /// `<div class="pagetoc-chapter">` .
+    pub fn chapters(&self, book: &mut Book, smart_punctuation: bool) -> Result<()> {
+        let mut exclude = GlobSetBuilder::new();
+        // patterns are checked in config
+        for glob in self.exclude.iter().filter_map(|p| Glob::new(p).ok()) {
+            exclude.add(glob);
+        }
+        let exclude = exclude.build().unwrap_or_else(|_| GlobSet::empty());
+        let mut result = Ok(());
+        book.for_each_mut(|item| {
+            let ch = match item {
+                BookItem::Chapter(ch) if result.is_ok() => ch,
+                _ => return,
+            };
+            let path = ch.source_path.clone().or_else(|| ch.path.clone());
+            let options = match self.chapter(&ch.content) {
+                Ok(_) if path.as_ref().is_some_and(|p| exclude.is_match(p)) => None,
+                Ok(options) => options,
+                Err((marker, reason)) => {
+                    let chapter = path.unwrap_or_default();
+                    result = Err(Error::InvalidMarker {
+                        chapter,
+                        marker,
+                        reason,
+                    });
+                    return;
+                }
+            };
+            let hidden =
+                |attrs: &str| format!(r#"<div class="pagetoc-chapter" {attrs} hidden></div>"#);
+            let head = match options {
+                Some(o) if self.static_toc => o.nav(&ch.content, smart_punctuation),
+                None if self.static_toc => None,
+                Some(o) if (o.min_level, o.max_level) == (self.min_level, self.max_level) => None,
+                Some(o) => Some(hidden(&format!(
+                    r#"data-min-level="{}" data-max-level="{}""#,
+                    o.min_level, o.max_level
+                ))),
+                None => Some(hidden("data-off")),
+            };
+            if let Some(head) = head {
+                ch.content = format!("{head}\n\n{}", ch.content);
+            }
+        });
+        result
+    }
+
+    /// The options for a chapter with `<!-- pagetoc: off -->` or
+    /// `<!-- pagetoc: min-level=2 max-level=3 -->` in html (not in code); `None` if it's off.
+    /// An invalid marker is returned with the reason.
+    fn chapter(&self, markdown: &str) -> std::result::Result<Option<Pagetoc>, (String, String)> {
+        let marker =
+            MARKER.get_or_init(|| Regex::new(r"<!--\s*pagetoc:(.*?)-->").expect("a valid regex"));
+        let mut options = Some(self.clone());
+        for event in mdbook::utils::new_cmark_parser(markdown, false) {
+            let html = match event {
+                Event::Html(html) | Event::InlineHtml(html) => html,
+                _ => continue,
+            };
+            for caps in marker.captures_iter(&html) {
+                let error = |reason: &str| (caps[0].to_string(), reason.to_string());
+                let words = caps[1].split(|c: char| c == ',' || c.is_whitespace());
+                for word in words.filter(|w| !w.is_empty()) {
+                    let level = |v: &str| match v.trim().parse() {
+                        Ok(n @ 1..=6) => Ok(n),
+                        _ => Err(error("expected a heading level from 1 to 6")),
+                    };
+                    let o = options.get_or_insert_with(|| self.clone());
+                    match word.split_once('=') {
+                        None if word == "off" => options = None,
+                        None if word == "on" => (),
+                        Some(("min-level", v)) => o.min_level = level(v)?,
+                        Some(("max-level", v)) => o.max_level = level(v)?,
+                        _ => {
+                            let reason = format!(
+                                "unknown `{word}` , expected `off` , `on` , `min-level=N` or \
+                                 `max-level=N`"
+                            );
+                            return Err(error(&reason));
+                        }
+                    }
+                }
+                if options.as_ref().is_some_and(|o| o.min_level > o.max_level) {
+                    return Err(error("`min-level` is greater than `max-level`"));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// The `<nav class="pagetoc">` of a chapter, linking to the headings with the ids mdBook gives
+    /// them (see `build_header_links` in mdBook); `None` if no heading is listed.
+    pub fn nav(&self, markdown: &str, smart_punctuation: bool) -> Option<String> {
+        let html = mdbook::utils::render_markdown(markdown, smart_punctuation);
+        let heading = HEADING.get_or_init(|| {
+            Regex::new(r#"<h(\d)(?: id="([^"]+)")?(?: class="([^"]+)")?>(.*?)</h\d>"#)
+                .expect("a valid regex")
+        });
+        let tag = TAG.get_or_init(|| Regex::new(r"<.*?>").expect("a valid regex"));
+        // every heading counts for unique ids, listed or not
+        let mut id_counter = HashMap::new();
+        let mut links = Vec::new();
+        for caps in heading.captures_iter(&html) {
+            let level: u8 = caps[1].parse().unwrap_or(0);
+            let content = &caps[4];
+            let id = match caps.get(2) {
+                Some(id) => id.as_str().to_string(),
+                None => unique_id_from_content(content, &mut id_counter),
+            };
+            if (self.min_level..=self.max_level).contains(&level) {
+                let link = format!(
+                    "<a href=\"#{id}\" style=\"--pagetoc-depth: {}\">{}</a>",
+                    level - self.min_level,
+                    tag.replace_all(content, "").trim()
+                );
+                links.push((level, link));
+            }
+        }
+        if links.is_empty() {
+            return None;
+        }
+        let links = match self.collapsible {
+            true => tree(&links),
+            false => links.iter().map(|(_, link)| format!("{link}\n")).collect(),
+        };
+        let links = format!("\n{links}");
+        let mut nav = self.sidetoc(&links);
+        if self.has_mobile() {
+            nav.push('\n');
+            nav.push_str(&self.mobiletoc(&links));
+        }
+        // an html block in markdown, so no blank line in it
+        Some(nav)
+    }
+
+    fn sidetoc(&self, links: &str) -> String {
+        let label = self.strings.html("on-this-page");
+        format!(
+            r#"<div class="sidetoc"><nav class="pagetoc" aria-label="{label}">{links}</nav></div>"#
+        )
+    }
+
+    fn mobiletoc(&self, links: &str) -> String {
+        let label = self.strings.html("on-this-page");
+        let nav = format!(r#"<nav class="pagetoc-mobile">{links}</nav>"#);
+        format!(r#"<details class="mobiletoc"><summary>{label}</summary>{nav}</details>"#)
+    }
+
+    /// The media query from the breakpoint on.
+    pub fn above(&self) -> String {
+        format!("@media only screen and (min-width:{})", self.breakpoint)
+    }
+}
+
+/// Whether a line in `index.hbs` is the pagetoc markup written by this tool.
+pub fn is_index_markup(line: &str) -> bool {
+    line.starts_with(r#"<div class="sidetoc">"#)
+        || line.starts_with(r#"<details class="mobiletoc">"#)
+}
+
+/// Whether a pagetoc file in the theme dir is written by this tool.
+pub fn is_generated(content: &str) -> bool {
+    content.contains(GENERATED) || LEGACY.contains(&manifest::hash(content.as_bytes()).as_str())
+}
+
+/// Nest the links in `<ul>` by their heading levels, as `pagetoc.js` does with `collapsible` .
+fn tree(links: &[(u8, String)]) -> String {
+    let mut html = String::from("<ul>\n");
+    // the levels of open `<li>` , and whether their `<ul>` is open
+    let mut open: Vec<(u8, bool)> = Vec::new();
+    for (level, link) in links {
+        while let Some((l, list)) = open.last_mut() {
+            if *l < *level {
+                if !*list {
+                    html.push_str("\n<ul>\n");
+                    *list = true;
+                }
+                break;
+            }
+            html.push_str(if *list { "</ul>\n</li>\n" } else { "</li>\n" });
+            open.pop();
+        }
+        html.push_str(&format!("<li>{link}"));
+        open.push((*level, false));
+    }
+    for (_, list) in open.iter().rev() {
+        html.push_str(if *list { "</ul>\n</li>\n" } else { "</li>\n" });
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+/// Keep or drop `{{#name}}...{{/name}}` (on their own lines) in the template.
+fn sections(mut text: String, flags: &[(&str, bool)]) -> String {
+    for (name, on) in flags {
+        let (open, close) = (format!("{{{{#{name}}}}}\n"), format!("{{{{/{name}}}}}\n"));
+        while let Some(start) = text.find(&open) {
+            let end = match text[start..].find(&close) {
+                Some(end) => start + end,
+                None => break,
+            };
+            let inner = match on {
+                true => text[start + open.len()..end].to_string(),
+                false => String::new(),
+            };
+            text.replace_range(start..end + close.len(), &inner);
+        }
+    }
+    text
+}
+
+/// Replace `{{key}}` in the template.
+fn render(mut text: String, vars: &[(&str, String)]) -> String {
+    for (key, value) in vars {
+        text = text.replace(&format!("{{{{{key}}}}}"), value);
+    }
+    text
+}
+
+fn template(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tree, Pagetoc};
+
+    /// the min and max levels of a chapter, `None` if it's off
+    fn levels(markdown: &str) -> Result<Option<(u8, u8)>, String> {
+        let pagetoc = Pagetoc {
+            min_level: 2,
+            max_level: 4,
+            ..Pagetoc::default()
+        };
+        match pagetoc.chapter(markdown) {
+            Ok(o) => Ok(o.map(|o| (o.min_level, o.max_level))),
+            Err((marker, _)) => Err(marker),
+        }
+    }
+
+    #[test]
+    fn markers() {
+        assert_eq!(levels("# A\n\ntext"), Ok(Some((2, 4))));
+        assert_eq!(levels("<!-- pagetoc: off -->\n# A"), Ok(None));
+        assert_eq!(levels("<!--pagetoc:off-->"), Ok(None));
+        assert_eq!(
+            levels("<!-- pagetoc: off -->\n<!-- pagetoc: on -->"),
+            Ok(Some((2, 4)))
+        );
+        assert_eq!(levels("<!-- pagetoc: min-level=1 -->"), Ok(Some((1, 4))));
+        assert_eq!(
+            levels("<!-- pagetoc: min-level=3, max-level=3 -->"),
+            Ok(Some((3, 3)))
+        );
+        assert_eq!(
+            levels("<!-- pagetoc: off max-level=6 -->"),
+            Ok(Some((2, 6)))
+        );
+        // in code, not a marker
+        assert_eq!(levels("`<!-- pagetoc: off -->`"), Ok(Some((2, 4))));
+        assert_eq!(levels("```\n<!-- pagetoc: off -->\n```"), Ok(Some((2, 4))));
+    }
+
+    #[test]
+    fn invalid_markers() {
+        for marker in [
+            "<!-- pagetoc: of -->",
+            "<!-- pagetoc: min-level=7 -->",
+            "<!-- pagetoc: max-level=two -->",
+            "<!-- pagetoc: min-level=5 -->",
+            "<!-- pagetoc: min-level=3 max-level=2 -->",
+        ] {
+            assert_eq!(levels(marker), Err(marker.to_string()));
+        }
+    }
+
+    /// the `href` of the links in a nav
+    fn hrefs(nav: &str) -> Vec<&str> {
+        let links = nav.split("<a href=\"#").skip(1);
+        links.filter_map(|a| a.split('"').next()).collect()
+    }
+
+    #[test]
+    fn ids_are_mdbooks() {
+        let markdown = "# A -- B `code`\n\n## A -- B `code`\n\n### Custom {#custom}\n";
+        let nav = Pagetoc::default().nav(markdown, true).unwrap();
+        assert_eq!(hrefs(&nav), ["a--b-code", "a--b-code-1", "custom"]);
+        assert!(nav.contains(r#"style="--pagetoc-depth: 1">A – B code</a>"#));
+        let nav = Pagetoc::default().nav(markdown, false).unwrap();
+        assert_eq!(hrefs(&nav), ["a----b-code", "a----b-code-1", "custom"]);
+    }
+
+    #[test]
+    fn unlisted_headings_count_for_ids() {
+        let pagetoc = Pagetoc {
+            min_level: 2,
+            ..Pagetoc::default()
+        };
+        let nav = pagetoc.nav("# Intro\n\n## Intro\n\n## Intro\n", false);
+        assert_eq!(hrefs(&nav.unwrap()), ["intro-1", "intro-2"]);
+        assert_eq!(pagetoc.nav("# Only a title\n\ntext", false), None);
+    }
+
+    #[test]
+    fn tree_nests_across_skipped_levels() {
+        let links: Vec<_> = [(1, "a"), (3, "b"), (2, "c"), (4, "d"), (1, "e")]
+            .iter()
+            .map(|&(level, link)| (level, link.to_string()))
+            .collect();
+        assert_eq!(
+            tree(&links),
+            "<ul>\n\
+             <li>a\n<ul>\n\
+             <li>b</li>\n\
+             <li>c\n<ul>\n\
+             <li>d</li>\n\
+             </ul>\n</li>\n\
+             </ul>\n</li>\n\
+             <li>e</li>\n\
+             </ul>\n"
+        );
+        // the first link deeper than a later one
+        let links = [(3, "a".to_string()), (1, "b".to_string())];
+        assert_eq!(tree(&links), "<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n");
+    }
+}
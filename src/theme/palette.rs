@@ -0,0 +1,143 @@
+//! `[preprocessor.theme.palette]` : derive the colour variables of a theme from a few seeds.
+//!
+//! Greys (sidebar, table, search) are mixed from the background towards the foreground, tints
+//! (quote, search results) from the background towards the accent, and text colours (links,
+//! inline code, active chapter) are pushed towards the foreground until they're readable.
+
+use super::color::Rgba;
+
+/// seeds in `[preprocessor.theme.palette]`
+pub const SEEDS: &[&str] = &["background", "foreground", "accent", "secondary"];
+
+/// WCAG AA for normal text
+const TEXT_CONTRAST: f64 = 4.5;
+
+/// The theme to apply to, and the variables (without `--`) with their values.
+#[derive(Clone, Debug)]
+pub struct Palette<'a> {
+    pub theme: &'a str,
+    pub colors: Vec<(&'static str, String)>,
+}
+
+/// `secondary` (for inline code) defaults to a mix of the foreground and the accent.
+pub fn derive(
+    bg: Rgba,
+    fg: Rgba,
+    accent: Rgba,
+    secondary: Option<Rgba>,
+) -> Vec<(&'static str, String)> {
+    let grey = |amount: f64| bg.mix(fg, amount);
+    let tint = |amount: f64| bg.mix(accent, amount);
+    let text = |c: Rgba, on: Rgba| c.with_contrast(on, TEXT_CONTRAST, fg);
+    let secondary = secondary.unwrap_or_else(|| fg.mix(accent, 0.35));
+    let sidebar_bg = grey(0.04);
+    let scheme = if bg.is_dark() { "dark" } else { "light" };
+
+    let colors = [
+        ("bg", bg),
+        ("fg", fg),
+        ("sidebar-bg", sidebar_bg),
+        ("sidebar-fg", fg.mix(bg, 0.05)),
+        ("sidebar-non-existant", grey(0.4)),
+        ("sidebar-active", text(accent, sidebar_bg)),
+        ("sidebar-spacer", grey(0.08)),
+        ("scrollbar", grey(0.45)),
+        ("icons", grey(0.55)),
+        ("icons-hover", fg),
+        ("links", text(accent, bg)),
+        ("inline-code-color", text(secondary, bg)),
+        ("theme-popup-bg", sidebar_bg),
+        ("theme-popup-border", grey(0.2)),
+        ("theme-hover", grey(0.1)),
+        ("quote-bg", tint(0.06)),
+        ("quote-border", tint(0.15)),
+        ("table-border-color", grey(0.05)),
+        ("table-header-bg", grey(0.2)),
+        ("table-alternate-bg", grey(0.03)),
+        ("searchbar-border-color", grey(0.35)),
+        ("searchbar-bg", grey(0.02)),
+        ("searchbar-fg", fg),
+        ("searchbar-shadow-color", grey(0.35)),
+        ("searchresults-header-fg", grey(0.6)),
+        ("searchresults-border-color", grey(0.45)),
+        ("searchresults-li-bg", tint(0.12)),
+        ("search-mark-bg", tint(0.35)),
+    ];
+    colors
+        .iter()
+        .map(|(name, c)| (*name, c.to_string()))
+        .chain(std::iter::once(("color-scheme", scheme.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derive, TEXT_CONTRAST};
+    use crate::theme::color::Rgba;
+
+    fn colors(
+        bg: &str,
+        fg: &str,
+        accent: &str,
+        secondary: Option<&str>,
+    ) -> Vec<(&'static str, String)> {
+        let c = |s: &str| Rgba::parse(s).unwrap();
+        derive(c(bg), c(fg), c(accent), secondary.map(c))
+    }
+
+    fn get<'c>(colors: &'c [(&str, String)], name: &str) -> &'c str {
+        let found = colors.iter().find(|(n, _)| *n == name);
+        found.map(|(_, v)| v.as_str()).unwrap()
+    }
+
+    #[test]
+    fn greys_and_tints_are_mixed() {
+        let c = colors("#ffffff", "#000000", "#0000ff", None);
+        assert_eq!(get(&c, "bg"), "#ffffff");
+        assert_eq!(get(&c, "fg"), "#000000");
+        assert_eq!(get(&c, "sidebar-bg"), "#f5f5f5");
+        assert_eq!(get(&c, "theme-popup-bg"), "#f5f5f5");
+        assert_eq!(get(&c, "table-header-bg"), "#cccccc");
+        assert_eq!(get(&c, "quote-bg"), "#f0f0ff");
+        assert_eq!(get(&c, "search-mark-bg"), "#a6a6ff");
+        assert_eq!(get(&c, "icons-hover"), "#000000");
+        assert_eq!(get(&c, "color-scheme"), "light");
+        let mut names: Vec<_> = c.iter().map(|(n, _)| *n).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), c.len());
+    }
+
+    #[test]
+    fn text_is_readable() {
+        // yellow links on white are pushed towards the foreground
+        let c = colors("#ffffff", "#333333", "#ffff00", Some("#ffcc00"));
+        for (name, bg) in [
+            ("links", "bg"),
+            ("inline-code-color", "bg"),
+            ("sidebar-active", "sidebar-bg"),
+        ] {
+            let color = Rgba::parse(get(&c, name)).unwrap();
+            let bg = Rgba::parse(get(&c, bg)).unwrap();
+            assert!(
+                color.contrast(bg) >= TEXT_CONTRAST,
+                "{}: {} on {}",
+                name,
+                color,
+                bg
+            );
+        }
+        // readable already: kept
+        let c = colors("#ffffff", "#000000", "#0000ff", Some("#800000"));
+        assert_eq!(get(&c, "links"), "#0000ff");
+        assert_eq!(get(&c, "inline-code-color"), "#800000");
+    }
+
+    #[test]
+    fn dark_backgrounds() {
+        let c = colors("#1e1e1e", "#dddddd", "#4fc1ff", None);
+        assert_eq!(get(&c, "color-scheme"), "dark");
+        let fg = Rgba::parse(get(&c, "sidebar-fg")).unwrap();
+        assert!(fg.contrast(Rgba::parse("#1e1e1e").unwrap()) > 10.0);
+    }
+}
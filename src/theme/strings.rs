@@ -0,0 +1,81 @@
+//! Labels this tool puts into the book, like "On this page" of the pagetoc, in the language of
+//! the book (`book.language`), and overridden by `[preprocessor.theme.strings]` .
+
+/// keys in `[preprocessor.theme.strings]` , in the order of the labels in `BUILTIN`
+pub const KEYS: &[&str] = &["on-this-page", "collapse-all", "toggle-subsections"];
+
+/// labels by language tags (lowercased), looked up by the whole tag and then shorter ones
+#[rustfmt::skip]
+const BUILTIN: &[(&str, [&str; 3])] = &[
+    ("en",      ["On this page",     "Collapse all",     "Toggle subsections"]),
+    ("zh",      ["本页目录",         "全部折叠",         "展开或折叠子章节"]),
+    ("zh-tw",   ["本頁目錄",         "全部摺疊",         "展開或摺疊子章節"]),
+    ("zh-hk",   ["本頁目錄",         "全部摺疊",         "展開或摺疊子章節"]),
+    ("zh-hant", ["本頁目錄",         "全部摺疊",         "展開或摺疊子章節"]),
+    ("ja",      ["このページの内容", "すべて折りたたむ", "サブセクションを開閉"]),
+    ("de",      ["Auf dieser Seite", "Alle einklappen",  "Unterabschnitte ein- oder ausklappen"]),
+    ("fr",      ["Sur cette page",   "Tout replier",     "Afficher ou masquer les sous-sections"]),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strings(Vec<String>);
+
+impl Default for Strings {
+    fn default() -> Self {
+        Strings(BUILTIN[0].1.iter().map(|s| s.to_string()).collect())
+    }
+}
+
+impl Strings {
+    /// The built-in labels for a language tag like `zh-CN` , `zh-Hant-TW` or `de` .
+    pub fn builtin(language: &str) -> Option<Self> {
+        let mut tag = language.trim().to_ascii_lowercase().replace('_', "-");
+        loop {
+            if let Some((_, labels)) = BUILTIN.iter().find(|(l, _)| *l == tag) {
+                return Some(Strings(labels.iter().map(|s| s.to_string()).collect()));
+            }
+            tag.truncate(tag.rfind('-')?);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> &str {
+        KEYS.iter()
+            .position(|k| *k == key)
+            .map_or("", |i| self.0[i].as_str())
+    }
+
+    /// Override a label; `false` if the key is unknown.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match KEYS.iter().position(|k| *k == key) {
+            Some(i) => {
+                self.0[i] = value.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// a label to put into html
+    pub fn html(&self, key: &str) -> String {
+        let mut html = String::new();
+        for c in self.get(key).chars() {
+            match c {
+                '&' => html.push_str("&amp;"),
+                '<' => html.push_str("&lt;"),
+                '>' => html.push_str("&gt;"),
+                '"' => html.push_str("&quot;"),
+                c => html.push(c),
+            }
+        }
+        html
+    }
+
+    /// the labels as a js object literal
+    pub fn js(&self) -> String {
+        let labels = KEYS.iter().zip(&self.0);
+        let map: serde_json::Map<_, _> = labels
+            .map(|(k, v)| (k.to_string(), v.clone().into()))
+            .collect();
+        serde_json::Value::Object(map).to_string()
+    }
+}
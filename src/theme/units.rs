@@ -0,0 +1,320 @@
+//! Check the values of items in `DEFAULT` against their [`ValueType`]s, and convert `px` into
+//! `rem` when `px-to-rem = true` .
+//!
+//! Math functions like `calc()` and `var()` are accepted as they are.
+
+use super::color;
+pub use super::ValueType;
+
+/// css units of lengths, and `%`
+const UNITS: &[&str] = &[
+    "px", "em", "rem", "%", "vw", "vh", "vmin", "vmax", "svw", "svh", "lvw", "lvh", "dvw", "dvh",
+    "ch", "ex", "cap", "ic", "lh", "rlh", "pt", "pc", "cm", "mm", "q", "in",
+];
+
+const FUNCTIONS: &[&str] = &["calc(", "var(", "min(", "max(", "clamp(", "env("];
+
+const FONT_SIZES: &[&str] = &[
+    "xx-small",
+    "x-small",
+    "small",
+    "medium",
+    "large",
+    "x-large",
+    "xx-large",
+    "xxx-large",
+    "smaller",
+    "larger",
+];
+
+const GLOBAL_KEYWORDS: &[&str] = &["inherit", "initial", "unset", "revert"];
+
+/// browsers' default font size of `html`
+const BROWSER_FONT_SIZE: f64 = 16.0;
+
+/// Check `value` against `ty` and normalize it (trimmed, lowercased and single-spaced).
+/// With `root_px` , `px` lengths are converted into `rem` .
+pub fn check(ty: ValueType, value: &str, root_px: Option<f64>) -> Result<String, String> {
+    let value = value.trim();
+    if ty == ValueType::Color {
+        return color::normalize(value);
+    } else if ty == ValueType::FontFamily {
+        return font_families(value);
+    } else if GLOBAL_KEYWORDS.contains(&value.to_ascii_lowercase().as_str()) {
+        return Ok(value.to_ascii_lowercase());
+    }
+    let tokens = tokens(value)?;
+    let max = if ty == ValueType::Shorthand { 4 } else { 1 };
+    if tokens.is_empty() {
+        return Err(format!("expected {}", expected(ty)));
+    } else if let [n, unit] = tokens[..] {
+        let unit = unit.to_ascii_lowercase();
+        if number(n).is_some_and(|(_, u)| u.is_empty()) && UNITS.contains(&unit.as_str()) {
+            let reason = format!("`{value}` has a space between the number and the unit");
+            return Err(reason);
+        }
+    }
+    if tokens.len() > max {
+        return Err(format!(
+            "`{value}` has too many values: expected {}",
+            expected(ty)
+        ));
+    }
+    tokens
+        .iter()
+        .map(|t| token(ty, t, root_px))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|v| v.join(" "))
+}
+
+/// The font size in px of a value checked as [`ValueType::FontSize`] on `html` , if it's known.
+pub fn font_size_px(value: &str) -> Option<f64> {
+    if value == "medium" {
+        return Some(BROWSER_FONT_SIZE);
+    }
+    match number(value)? {
+        (n, "px") => Some(n),
+        (n, "%") => Some(n / 100.0 * BROWSER_FONT_SIZE),
+        (n, "em" | "rem") => Some(n * BROWSER_FONT_SIZE),
+        _ => None,
+    }
+}
+
+fn expected(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::Length => "a length or a percentage, like `140px` or `80%`",
+        ValueType::LengthOrAuto => "a length, a percentage, `auto` or `none`",
+        ValueType::Shorthand => "1 to 4 lengths or percentages, like `0 10px`",
+        ValueType::LineHeight => "`normal`, a number, a length or a percentage, like `1.5`",
+        ValueType::FontSize => "a length, a percentage or a keyword, like `1.5rem` or `small`",
+        ValueType::FontFamily => "font names, like `Inter, sans-serif`",
+        ValueType::Color => "a colour",
+    }
+}
+
+fn token(ty: ValueType, t: &str, root_px: Option<f64>) -> Result<String, String> {
+    let lower = t.to_ascii_lowercase();
+    if FUNCTIONS.iter().any(|f| lower.starts_with(f)) && t.ends_with(')') {
+        return Ok(t.to_string()); // variable names are case-sensitive
+    }
+    let t = lower.as_str();
+    match (ty, t) {
+        (ValueType::LengthOrAuto, "auto" | "none") | (ValueType::LineHeight, "normal") => {
+            return Ok(t.to_string())
+        }
+        (ValueType::FontSize, k) if FONT_SIZES.contains(&k) => return Ok(t.to_string()),
+        _ => (),
+    }
+    let (n, unit) = number(t).ok_or_else(|| format!("`{t}` is not {}", expected(ty)))?;
+    match unit {
+        "" if ty == ValueType::LineHeight => Ok(t.to_string()),
+        "" if n == 0.0 => Ok(String::from("0")),
+        "" => Err(format!("`{t}` needs a unit, like `{t}px`")),
+        "px" => Ok(root_px.map_or_else(|| t.to_string(), |root| rem(n / root))),
+        u if UNITS.contains(&u) => Ok(t.to_string()),
+        u => Err(format!("`{u}` in `{t}` is not a css unit")),
+    }
+}
+
+/// Check the names in a `font-family` value, and join them with `, ` .
+/// Names with spaces are fine unquoted, but ones with other chars (like `1`) need quotes.
+fn font_families(value: &str) -> Result<String, String> {
+    let (mut names, mut quote, mut depth, mut start) = (Vec::new(), None, 0i32, 0);
+    for (i, c) in value.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                names.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if quote.is_some() || depth != 0 {
+        return Err(format!("`{value}` has an unclosed quote or parenthesis"));
+    }
+    names.push(&value[start..]);
+    let ident = |w: &str| {
+        !w.starts_with(|c: char| c.is_ascii_digit())
+            && w.chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    };
+    let check = |name: &str| {
+        let name = name.trim();
+        if name.is_empty() {
+            Err(format!("`{value}` has an empty font name"))
+        } else if name.starts_with(['"', '\'']) || name.starts_with("var(") {
+            Ok(name.to_string())
+        } else if name.split_whitespace().all(ident) {
+            Ok(name.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            Err(format!("`{name}` needs quotes, like `\"{name}\"`"))
+        }
+    };
+    let names = names.into_iter().map(check);
+    names.collect::<Result<Vec<_>, _>>().map(|v| v.join(", "))
+}
+
+/// at most 4 decimals, without trailing zeros
+fn rem(n: f64) -> String {
+    let s = format!("{n:.4}");
+    format!("{}rem", s.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/// Split a value at whitespace outside parentheses.
+fn tokens(value: &str) -> Result<Vec<&str>, String> {
+    let (mut tokens, mut depth, mut start) = (Vec::new(), 0i32, None);
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                tokens.extend(start.take().map(|s| &value[s..i]));
+                continue;
+            }
+            _ => (),
+        }
+        if depth < 0 {
+            break;
+        }
+        start.get_or_insert(i);
+    }
+    if depth != 0 {
+        return Err(format!("`{value}` has unbalanced parentheses"));
+    }
+    tokens.extend(start.map(|s| &value[s..]));
+    Ok(tokens)
+}
+
+/// `1.5em` into `(1.5, "em")` , and `1e3px` into `(1000.0, "px")`
+fn number(t: &str) -> Option<(f64, &str)> {
+    let mut end = t
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(t.len(), |(i, _)| i);
+    // an exponent, but not the `e` of units like `em`
+    if let Some(exp) = t[end..].strip_prefix(['e', 'E']) {
+        let sign = usize::from(exp.starts_with(['-', '+']));
+        let digits = exp[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(exp.len() - sign);
+        if digits > 0 {
+            end += 1 + sign + digits;
+        }
+    }
+    let n = t[..end].parse::<f64>().ok()?;
+    Some((n, &t[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, font_size_px, ValueType};
+
+    fn ok(ty: ValueType, value: &str) -> String {
+        check(ty, value, None).unwrap()
+    }
+
+    fn err(ty: ValueType, value: &str) -> String {
+        check(ty, value, None).unwrap_err()
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(ok(ValueType::Length, " 140PX "), "140px");
+        assert_eq!(ok(ValueType::Length, "82%"), "82%");
+        assert_eq!(ok(ValueType::Length, "0"), "0");
+        assert_eq!(ok(ValueType::Length, "-1.5em"), "-1.5em");
+        assert_eq!(
+            ok(ValueType::Length, "calc(100% - 2em)"),
+            "calc(100% - 2em)"
+        );
+        assert_eq!(ok(ValueType::Length, "var(--Width)"), "var(--Width)");
+        assert_eq!(ok(ValueType::Length, "inherit"), "inherit");
+        assert_eq!(ok(ValueType::LengthOrAuto, "auto"), "auto");
+        assert_eq!(
+            err(ValueType::Length, "10"),
+            "`10` needs a unit, like `10px`"
+        );
+        assert_eq!(
+            err(ValueType::Length, "10furlongs"),
+            "`furlongs` in `10furlongs` is not a css unit"
+        );
+        assert!(err(ValueType::Length, "fifteen").starts_with("`fifteen` is not a length"));
+        assert!(err(ValueType::Length, "auto").starts_with("`auto` is not a length"));
+        assert!(err(ValueType::Length, "calc(1px").contains("unbalanced"));
+        assert!(err(ValueType::Length, "").starts_with("expected"));
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(ok(ValueType::Length, "1e3px"), "1e3px");
+        assert_eq!(ok(ValueType::Length, "2.5E-1em"), "2.5e-1em");
+        assert_eq!(ok(ValueType::LineHeight, "1e0"), "1e0");
+        assert_eq!(ok(ValueType::Length, "1em"), "1em");
+        assert_eq!(ok(ValueType::Length, "2ex"), "2ex");
+        assert_eq!(
+            check(ValueType::Length, "1e1px", Some(10.0)).unwrap(),
+            "1rem"
+        );
+        assert!(err(ValueType::Length, "1e").contains("is not a css unit"));
+    }
+
+    #[test]
+    fn spaces_and_counts() {
+        assert_eq!(
+            err(ValueType::Length, "13 %"),
+            "`13 %` has a space between the number and the unit"
+        );
+        assert_eq!(
+            err(ValueType::Length, "13 px"),
+            "`13 px` has a space between the number and the unit"
+        );
+        assert_eq!(ok(ValueType::Shorthand, "0  10px"), "0 10px");
+        assert_eq!(
+            ok(ValueType::Shorthand, "1px 2px 3px 4px"),
+            "1px 2px 3px 4px"
+        );
+        assert!(err(ValueType::Shorthand, "1px 2px 3px 4px 5px").contains("too many values"));
+        assert!(err(ValueType::Length, "1px 2px").contains("too many values"));
+    }
+
+    #[test]
+    fn line_heights_and_font_sizes() {
+        assert_eq!(ok(ValueType::LineHeight, "1.5"), "1.5");
+        assert_eq!(ok(ValueType::LineHeight, "Normal"), "normal");
+        assert_eq!(ok(ValueType::FontSize, "small"), "small");
+        assert!(err(ValueType::FontSize, "tiny").starts_with("`tiny` is not a length"));
+        assert_eq!(font_size_px("62.5%"), Some(10.0));
+        assert_eq!(font_size_px("medium"), Some(16.0));
+        assert_eq!(font_size_px("1.25rem"), Some(20.0));
+        assert_eq!(font_size_px("small"), None);
+    }
+
+    #[test]
+    fn px_to_rem() {
+        let rem = |value: &str| check(ValueType::Shorthand, value, Some(16.0)).unwrap();
+        assert_eq!(rem("16px"), "1rem");
+        assert_eq!(rem("0 10px"), "0 0.625rem");
+        assert_eq!(rem("1px"), "0.0625rem");
+        assert_eq!(rem("3px 2em 50%"), "0.1875rem 2em 50%");
+    }
+
+    #[test]
+    fn font_families() {
+        let fonts = |value: &str| check(ValueType::FontFamily, value, None);
+        assert_eq!(fonts("Open  Sans,serif").unwrap(), "Open Sans, serif");
+        assert_eq!(fonts("'Inter 2', var(--f)").unwrap(), "'Inter 2', var(--f)");
+        assert_eq!(
+            fonts("Inter 2").unwrap_err(),
+            "`Inter 2` needs quotes, like `\"Inter 2\"`"
+        );
+        assert!(fonts("Inter,,serif")
+            .unwrap_err()
+            .contains("empty font name"));
+        assert!(fonts("'Inter").unwrap_err().contains("unclosed quote"));
+    }
+}